@@ -1,4 +1,3 @@
-#[cfg(feature = "glutin_window")]
 fn main() {
     use gl_generator::{Api, Fallbacks, Profile, Registry};
     use std::env;
@@ -14,6 +13,3 @@ fn main() {
         .write_bindings(gl_generator::StructGenerator, &mut file)
         .unwrap();
 }
-
-#[cfg(feature = "gtk_window")]
-fn main() {}
@@ -0,0 +1,115 @@
+//! Measures how long lepton takes to draw a frame, by drawing the page over
+//! and over into an offscreen GL context with no window involved, then
+//! printing how the time splits between the parts of `App::paint`.
+//!
+//! Run it with `--release`; it takes the same options as lepton itself.
+//!
+//! To compare two builds, set `FRAME_TIMING_BASELINE=<path>`: the first run
+//! saves its medians there, and later runs print theirs next to them.
+
+use lepton::app::App;
+use lepton::backend::headless::HeadlessBackend;
+use lepton::backend::{BackendEvent, LoopControl, WindowBackend};
+use lepton::frame::FrameTimings;
+use servo::webrender_api::units::DeviceIntSize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Frames drawn while the page loads, which are left out.
+const WARM_UP_FRAMES: usize = 120;
+const MEASURED_FRAMES: usize = 600;
+
+/// The median of each phase in a previous run, by phase name.
+type Baseline = HashMap<String, Duration>;
+
+fn read_baseline(text: &str) -> Baseline {
+    text.lines()
+        .filter_map(|line| {
+            let (name, nanos) = line.rsplit_once(' ')?;
+            Some((name.to_owned(), Duration::from_nanos(nanos.parse().ok()?)))
+        })
+        .collect()
+}
+
+fn print_stats(name: &str, mut samples: Vec<Duration>, baseline: Option<&Baseline>) -> Duration {
+    samples.sort();
+    let mean = samples.iter().sum::<Duration>() / samples.len() as u32;
+    let percentile = |p: usize| samples[(samples.len() - 1) * p / 100];
+    let median = percentile(50);
+    let before = match baseline.and_then(|baseline| baseline.get(name)) {
+        Some(before) => format!(
+            "  before {:>9.3?} ({:+.1}%)",
+            before,
+            (median.as_secs_f64() / before.as_secs_f64() - 1.0) * 100.0
+        ),
+        None => String::new(),
+    };
+    println!(
+        "{:>14}  mean {:>9.3?}  median {:>9.3?}  p95 {:>9.3?}  max {:>9.3?}{}",
+        name,
+        mean,
+        median,
+        percentile(95),
+        percentile(100),
+        before
+    );
+    median
+}
+
+fn main() {
+    let config = lepton::init();
+    let backend = HeadlessBackend::with_gl(DeviceIntSize::new(1024, 768));
+    let mut app = App::new(&backend, config);
+    let mut frames: Vec<FrameTimings> = vec![];
+    let mut drawn = 0;
+
+    backend.run(move |backend, event| {
+        let redraw = matches!(event, BackendEvent::RedrawRequested);
+        if app.handle_event(backend, event) == LoopControl::Exit {
+            return LoopControl::Exit;
+        }
+        if redraw {
+            drawn += 1;
+            if drawn > WARM_UP_FRAMES {
+                frames.extend(app.last_frame_timings());
+            }
+        }
+        if frames.len() == MEASURED_FRAMES {
+            println!("{} frames:", frames.len());
+            let baseline_path = std::env::var_os("FRAME_TIMING_BASELINE");
+            let baseline = baseline_path
+                .as_ref()
+                .and_then(|path| std::fs::read_to_string(path).ok())
+                .map(|text| read_baseline(&text));
+            let phase = |get: fn(&FrameTimings) -> Duration| -> Vec<Duration> {
+                frames.iter().map(get).collect()
+            };
+            let phases: [(&str, fn(&FrameTimings) -> Duration); 6] = [
+                ("servo present", |timings| timings.servo_present),
+                ("bind", |timings| timings.bind),
+                ("draw", |timings| timings.draw),
+                ("unbind", |timings| timings.unbind),
+                ("present", |timings| timings.present),
+                ("total", FrameTimings::total),
+            ];
+            let mut medians = String::new();
+            for (name, get) in phases.iter().copied() {
+                let median = print_stats(name, phase(get), baseline.as_ref());
+                medians += &format!("{} {}\n", name, median.as_nanos());
+            }
+            if let (Some(path), None) = (&baseline_path, &baseline) {
+                match std::fs::write(path, medians) {
+                    Ok(()) => println!(
+                        "Saved these medians to {:?} to compare later runs with",
+                        path
+                    ),
+                    Err(error) => eprintln!("Failed to save the medians to {:?}: {}", path, error),
+                }
+            }
+            return LoopControl::Exit;
+        }
+        // Draw again straight away, whether or not the page changed.
+        backend.request_redraw();
+        LoopControl::Poll
+    });
+}
@@ -0,0 +1,71 @@
+//! Measures what bursts of mouse moves cost, by replaying a synthetic trace
+//! of them against a page in headless mode: the pointer circles the page,
+//! moving as often as a 1000 Hz mouse does between two frames at 60 Hz.
+//!
+//! Run it with `--release`, once as is and once with
+//! `--no-coalesce-mouse-moves`, to compare; it takes the same options as
+//! lepton itself.
+
+use lepton::app::App;
+use lepton::backend::headless::HeadlessBackend;
+use lepton::backend::{BackendEvent, LoopControl};
+use servo::webrender_api::units::{DeviceIntSize, DevicePoint};
+use std::time::{Duration, Instant};
+
+/// Time left for the page to load before the trace starts.
+const WARM_UP: Duration = Duration::from_secs(3);
+const BURSTS: usize = 300;
+const MOVES_PER_BURST: usize = 16;
+
+/// Where the pointer is at a given step of the trace.
+fn trace_point(size: DeviceIntSize, step: usize) -> DevicePoint {
+    let angle = step as f32 / 100.0;
+    let (sin, cos) = angle.sin_cos();
+    let center = size.to_f32() / 2.0;
+    let radius = center.width.min(center.height) * 0.8;
+    DevicePoint::new(center.width + radius * cos, center.height + radius * sin)
+}
+
+fn main() {
+    let config = lepton::init();
+    let size = DeviceIntSize::new(1024, 768);
+    let backend = HeadlessBackend::new(size);
+    let mut app = App::new(&backend, config);
+    let start = Instant::now();
+    let mut stats_before = None;
+    let mut bursts = 0;
+    let mut busy = Duration::ZERO;
+
+    backend.run(move |backend, event| {
+        if app.handle_event(backend, event) == LoopControl::Exit {
+            return LoopControl::Exit;
+        }
+        // Keep the loop turning, so the trace goes on whether or not the
+        // page has anything to do.
+        if start.elapsed() < WARM_UP {
+            return LoopControl::Poll;
+        }
+        let stats_before = *stats_before.get_or_insert_with(|| app.input_stats());
+
+        if bursts == BURSTS {
+            let stats = app.input_stats();
+            let moves = stats.moves - stats_before.moves;
+            let coalesced = stats.coalesced_moves - stats_before.coalesced_moves;
+            println!("{} bursts of {} mouse moves:", BURSTS, MOVES_PER_BURST);
+            println!("  moves passed on to Servo: {} of {}", moves - coalesced, moves);
+            println!("  batches handed to Servo:  {}", stats.batches - stats_before.batches);
+            println!("  time per burst:           {:.3?}", busy / BURSTS as u32);
+            return LoopControl::Exit;
+        }
+
+        let burst_start = Instant::now();
+        for index in 0..MOVES_PER_BURST {
+            let point = trace_point(size, bursts * MOVES_PER_BURST + index);
+            app.handle_event(backend, BackendEvent::CursorMoved(point));
+        }
+        app.handle_event(backend, BackendEvent::EventsCleared);
+        busy += burst_start.elapsed();
+        bursts += 1;
+        LoopControl::Poll
+    });
+}
@@ -0,0 +1,331 @@
+//! Shows the page on a quad swinging around in 3D, drawn by the embedder
+//! with its own GL code, and still lets it be clicked: the cursor is cast as
+//! a ray into the scene and where it hits the quad is turned back into a
+//! point in the page.
+//!
+//! Like lepton-glutin, this only runs on macOS for now.
+
+// Elsewhere, all there is to it is a `main` that says so.
+#![cfg_attr(not(target_os = "macos"), allow(dead_code, unused_imports))]
+
+use gleam::gl::{self, Gl};
+use lepton::app::App;
+#[cfg(target_os = "macos")]
+use lepton::backend::glutin_window::GlutinBackend;
+use lepton::backend::{BackendEvent, LoopControl, WindowBackend};
+use lepton::frame::{FrameTexture, PaintContext, Painter};
+use servo::script_traits::TouchEventType;
+use servo::webrender_api::units::{DeviceIntSize, DevicePoint};
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Instant;
+
+/// Not part of GLES, but what IOSurface-backed surfaces are bound as.
+const TEXTURE_RECTANGLE: gl::GLenum = 0x84F5;
+
+const FIELD_OF_VIEW: f32 = std::f32::consts::FRAC_PI_4;
+/// How far the camera is from the middle of the quad.
+const CAMERA_DISTANCE: f32 = 3.0;
+
+const VERTEX_SHADER: &str = "
+attribute vec2 aPosition;
+uniform mat4 uTransform;
+uniform vec2 uHalfSize;
+uniform vec2 uTexScale;
+varying vec2 vTexCoord;
+
+void main() {
+    vTexCoord = aPosition * uTexScale;
+    gl_Position = uTransform * vec4((aPosition * 2.0 - 1.0) * uHalfSize, 0.0, 1.0);
+}
+";
+
+const FRAGMENT_SHADER: &str = "
+uniform SAMPLER uTexture;
+varying vec2 vTexCoord;
+
+void main() {
+    gl_FragColor = TEXTURE(uTexture, vTexCoord);
+}
+";
+
+/// The corners of the unit square, as a triangle strip.
+const QUAD: [f32; 8] = [0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0];
+
+/// A column-major 4x4 matrix, as GL takes them.
+type Matrix = [f32; 16];
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut result = [0.0; 16];
+    for column in 0..4 {
+        for row in 0..4 {
+            result[column * 4 + row] = (0..4).map(|i| a[i * 4 + row] * b[column * 4 + i]).sum();
+        }
+    }
+    result
+}
+
+fn perspective(aspect: f32) -> Matrix {
+    let (near, far) = (0.1, 100.0);
+    let f = 1.0 / (FIELD_OF_VIEW / 2.0).tan();
+    #[rustfmt::skip]
+    let matrix = [
+        f / aspect, 0.0, 0.0, 0.0,
+        0.0, f, 0.0, 0.0,
+        0.0, 0.0, (far + near) / (near - far), -1.0,
+        0.0, 0.0, 2.0 * far * near / (near - far), 0.0,
+    ];
+    matrix
+}
+
+/// Moves the scene away from the camera, which sits at the origin.
+fn view() -> Matrix {
+    #[rustfmt::skip]
+    let matrix = [
+        1.0, 0.0, 0.0, 0.0,
+        0.0, 1.0, 0.0, 0.0,
+        0.0, 0.0, 1.0, 0.0,
+        0.0, 0.0, -CAMERA_DISTANCE, 1.0,
+    ];
+    matrix
+}
+
+fn rotation_y(angle: f32) -> Matrix {
+    let (sin, cos) = angle.sin_cos();
+    #[rustfmt::skip]
+    let matrix = [
+        cos, 0.0, -sin, 0.0,
+        0.0, 1.0, 0.0, 0.0,
+        sin, 0.0, cos, 0.0,
+        0.0, 0.0, 0.0, 1.0,
+    ];
+    matrix
+}
+
+/// Half the quad's width and height. It is two units wide and as tall as
+/// the page's shape requires.
+fn half_size(page_size: DeviceIntSize) -> (f32, f32) {
+    let page_size = page_size.to_f32();
+    (1.0, page_size.height / page_size.width.max(1.0))
+}
+
+/// Finds where the ray through `point` in the window meets the quad, in
+/// texture coordinates from 0 to 1 with the origin at the bottom left.
+fn hit_test(
+    point: DevicePoint,
+    window_size: DeviceIntSize,
+    angle: f32,
+    (half_width, half_height): (f32, f32),
+) -> Option<(f32, f32)> {
+    let window_size = window_size.to_f32();
+    let x = point.x / window_size.width * 2.0 - 1.0;
+    let y = 1.0 - point.y / window_size.height * 2.0;
+    let tan = (FIELD_OF_VIEW / 2.0).tan();
+    let aspect = window_size.width / window_size.height;
+    let direction = [x * tan * aspect, y * tan, -1.0];
+    let origin = [0.0, 0.0, CAMERA_DISTANCE];
+
+    // Undo the quad's rotation, so it lies in the z = 0 plane.
+    let (sin, cos) = angle.sin_cos();
+    let unrotate = |[x, y, z]: [f32; 3]| [x * cos - z * sin, y, x * sin + z * cos];
+    let direction = unrotate(direction);
+    let origin = unrotate(origin);
+    if direction[2].abs() < f32::EPSILON {
+        return None;
+    }
+    let distance = -origin[2] / direction[2];
+    if distance < 0.0 {
+        return None;
+    }
+    let s = ((origin[0] + distance * direction[0]) / half_width + 1.0) / 2.0;
+    let t = ((origin[1] + distance * direction[1]) / half_height + 1.0) / 2.0;
+    ((0.0..=1.0).contains(&s) && (0.0..=1.0).contains(&t)).then_some((s, t))
+}
+
+fn compile_shader(gl: &dyn Gl, kind: gl::GLenum, source: &str) -> gl::GLuint {
+    let shader = gl.create_shader(kind);
+    gl.shader_source(shader, &[source.as_bytes()]);
+    gl.compile_shader(shader);
+    let mut status = [0];
+    unsafe { gl.get_shader_iv(shader, gl::COMPILE_STATUS, &mut status) };
+    if status[0] == 0 {
+        panic!("Shader failed to compile: {}", gl.get_shader_info_log(shader));
+    }
+    shader
+}
+
+struct Scene {
+    gl: Rc<dyn Gl>,
+    program: gl::GLuint,
+    /// The texture target the program samples from.
+    target: gl::GLenum,
+    vertex_array: gl::GLuint,
+}
+
+impl Scene {
+    fn new(gl: Rc<dyn Gl>, target: gl::GLenum) -> Scene {
+        let is_gles = gl.get_string(gl::VERSION).starts_with("OpenGL ES");
+        let (vertex_prefix, fragment_prefix) = if is_gles {
+            ("#version 100\n", "#version 100\nprecision mediump float;\n")
+        } else {
+            (
+                "#version 150\n#define attribute in\n#define varying out\n",
+                "#version 150\n#define varying in\nout vec4 fragColor;\n\
+                 #define gl_FragColor fragColor\n",
+            )
+        };
+        let (sampler, texture) = match (target, is_gles) {
+            (TEXTURE_RECTANGLE, _) => ("sampler2DRect", "texture"),
+            (_, true) => ("sampler2D", "texture2D"),
+            (_, false) => ("sampler2D", "texture"),
+        };
+        let vertex_source = format!("{}{}", vertex_prefix, VERTEX_SHADER);
+        let fragment_source = format!(
+            "{}#define SAMPLER {}\n#define TEXTURE {}\n{}",
+            fragment_prefix, sampler, texture, FRAGMENT_SHADER
+        );
+
+        let program = gl.create_program();
+        let vertex_shader = compile_shader(&*gl, gl::VERTEX_SHADER, &vertex_source);
+        let fragment_shader = compile_shader(&*gl, gl::FRAGMENT_SHADER, &fragment_source);
+        gl.attach_shader(program, vertex_shader);
+        gl.attach_shader(program, fragment_shader);
+        gl.bind_attrib_location(program, 0, "aPosition");
+        gl.link_program(program);
+        gl.delete_shader(vertex_shader);
+        gl.delete_shader(fragment_shader);
+        let mut status = [0];
+        unsafe { gl.get_program_iv(program, gl::LINK_STATUS, &mut status) };
+        if status[0] == 0 {
+            panic!("Shaders failed to link: {}", gl.get_program_info_log(program));
+        }
+
+        let vertex_array = gl.gen_vertex_arrays(1)[0];
+        gl.bind_vertex_array(vertex_array);
+        let buffer = gl.gen_buffers(1)[0];
+        gl.bind_buffer(gl::ARRAY_BUFFER, buffer);
+        gl::buffer_data(&*gl, gl::ARRAY_BUFFER, &QUAD, gl::STATIC_DRAW);
+        gl.enable_vertex_attrib_array(0);
+        gl.vertex_attrib_pointer(0, 2, gl::FLOAT, false, 0, 0);
+        gl.bind_vertex_array(0);
+        gl.bind_buffer(gl::ARRAY_BUFFER, 0);
+
+        Scene {
+            gl,
+            program,
+            target,
+            vertex_array,
+        }
+    }
+}
+
+/// Draws the page on the quad, and tells the event loop how it is turned.
+struct SwingingQuad {
+    scene: Option<Scene>,
+    start: Instant,
+    angle: Rc<Cell<f32>>,
+}
+
+impl Painter for SwingingQuad {
+    fn paint(&mut self, frame: &FrameTexture, context: &PaintContext) {
+        let scene = self.scene.get_or_insert_with(|| {
+            let gl = unsafe { gl::GlFns::load_with(|name| context.get_proc_address(name)) };
+            Scene::new(gl, frame.target)
+        });
+        let gl = &scene.gl;
+
+        let angle = (self.start.elapsed().as_secs_f32() * 0.8).sin() * 0.6;
+        self.angle.set(angle);
+        let window_size = context.window_size.to_f32();
+        let transform = multiply(
+            &perspective(window_size.width / window_size.height),
+            &multiply(&view(), &rotation_y(angle)),
+        );
+        let (half_width, half_height) = half_size(frame.size);
+        // Rectangle textures are addressed in texels rather than 0 to 1.
+        let (scale_x, scale_y) = if frame.target == TEXTURE_RECTANGLE {
+            (frame.size.width as f32, frame.size.height as f32)
+        } else {
+            (1.0, 1.0)
+        };
+
+        gl.viewport(0, 0, context.window_size.width, context.window_size.height);
+        gl.clear_color(0.1, 0.1, 0.15, 1.0);
+        gl.clear(gl::COLOR_BUFFER_BIT);
+        gl.disable(gl::CULL_FACE);
+        gl.use_program(scene.program);
+        let uniform = |name| gl.get_uniform_location(scene.program, name);
+        gl.uniform_matrix_4fv(uniform("uTransform"), false, &transform);
+        gl.uniform_2f(uniform("uHalfSize"), half_width, half_height);
+        gl.uniform_2f(uniform("uTexScale"), scale_x, scale_y);
+        gl.uniform_1i(uniform("uTexture"), 0);
+        gl.active_texture(gl::TEXTURE0);
+        gl.bind_texture(scene.target, frame.id);
+        gl.tex_parameter_i(scene.target, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+        gl.tex_parameter_i(scene.target, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+        gl.bind_vertex_array(scene.vertex_array);
+        gl.draw_arrays(gl::TRIANGLE_STRIP, 0, 4);
+        gl.bind_vertex_array(0);
+        gl.bind_texture(scene.target, 0);
+        gl.use_program(0);
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn main() {
+    eprintln!("rotating-quad only runs on macOS for now");
+    std::process::exit(1);
+}
+
+#[cfg(target_os = "macos")]
+fn main() {
+    let config = lepton::init();
+    let backend = GlutinBackend::new("lepton on a quad");
+    let mut app = App::new(&backend, config);
+    let angle = Rc::new(Cell::new(0.0));
+    app.set_painter(Box::new(SwingingQuad {
+        scene: None,
+        start: Instant::now(),
+        angle: angle.clone(),
+    }));
+
+    // Where the page is told the cursor went when it leaves the quad.
+    let off_page = DevicePoint::new(-1.0, -1.0);
+    let mut over_quad = false;
+    backend.run(move |backend, event| {
+        // Positions off the quad are dropped, so the page only sees the
+        // cursor while it is over the quad.
+        let to_page = |point| {
+            hit_test(point, backend.inner_size(), angle.get(), half_size(app.page_size()))
+                .map(|(s, t)| app.page_point_from_texture_coords(s, t))
+        };
+        let event = match event {
+            BackendEvent::CursorMoved(point) => match to_page(point) {
+                Some(point) => {
+                    over_quad = true;
+                    BackendEvent::CursorMoved(point)
+                }
+                // Moving off the quad once, so nothing stays hovered.
+                None if std::mem::take(&mut over_quad) => BackendEvent::CursorMoved(off_page),
+                None => return LoopControl::Poll,
+            },
+            BackendEvent::Touch { id, phase, position } => match (to_page(position), phase) {
+                (Some(position), _) => BackendEvent::Touch { id, phase, position },
+                // Fingers lifted off the quad still have to let go.
+                (None, TouchEventType::Up | TouchEventType::Cancel) => BackendEvent::Touch {
+                    id,
+                    phase: TouchEventType::Cancel,
+                    position: off_page,
+                },
+                (None, _) => return LoopControl::Poll,
+            },
+            event => event,
+        };
+        // Keep drawing, since the quad never stops moving.
+        backend.request_redraw();
+        match app.handle_event(backend, event) {
+            LoopControl::Exit => LoopControl::Exit,
+            _ => LoopControl::Poll,
+        }
+    });
+}
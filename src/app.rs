@@ -0,0 +1,1821 @@
+//! The part of lepton that drives Servo. It only talks to the window through
+//! `WindowBackend`, so it is shared by every front-end.
+
+use euclid::default::Point2D;
+use euclid::Scale;
+use ipc_channel::ipc::IpcSender;
+use servo::base::id::{BrowsingContextId, WebViewId};
+use servo::compositing::windowing::{
+    AnimationState, EmbedderCoordinates, EmbedderEvent, EmbedderMethods, MouseWindowEvent,
+    WindowMethods,
+};
+use servo::compositing::CompositeTarget;
+use servo::config::opts::Opts;
+use servo::config::prefs::Preferences;
+use servo::keyboard_types::{
+    CompositionEvent, CompositionState, Key, KeyState, KeyboardEvent, Modifiers,
+};
+use servo::script_traits::{
+    TouchEventType, TouchId, TraversalDirection, WebDriverCommandMsg, WheelDelta,
+};
+use servo::servo_url::ServoUrl;
+use servo::webrender_api::units::{
+    DeviceIntPoint, DeviceIntRect, DeviceIntSize, DevicePoint, DeviceRect, LayoutVector2D,
+};
+use servo::webrender_api::ScrollLocation;
+use servo::webrender_traits::rendering_context::SurfmanRenderingContext;
+use servo::{
+    CompositorEventVariant, ContextMenuResult, EmbedderMsg, EventLoopWaker, FilterPattern,
+    MouseButton, PromptDefinition, SelectElementOption, SelectElementOptionOrOptgroup, Servo,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Instant;
+
+use crate::backend::{BackendEvent, ImeEvent, LoopControl, WindowBackend, LINE_HEIGHT};
+use crate::bridge::{self, Bridge, BridgeMessage, Envelope};
+use crate::config::Config;
+use crate::context_menu::{ContextMenuAction, ContextMenuCallback, ContextMenuTarget};
+use crate::coordinates::{
+    device_rect_to_independent, device_size_to_independent, rescale_device_point,
+};
+use crate::file_picker::{accepted_extensions, scripted_selection};
+use crate::frame::{FrameTexture, FrameTimings, PaintContext, Painter};
+use crate::input::{EventQueue, InputStats};
+use crate::logging;
+use crate::menu::{GlMenu, MenuItem, MenuResponse};
+use crate::overlay::font_for_scale;
+use crate::perf::PerfRecorder;
+use crate::quad::Quad;
+use crate::recording::{self, Header, RecordedEvent, Recorder, Replayer};
+use crate::scheduler::{FrameScheduler, FrameStats};
+use crate::script::{JavaScriptError, JavaScriptResult, ScriptEvaluator};
+use crate::support;
+use crate::touch::TouchTracker;
+use crate::zoom::ZoomLevels;
+
+struct Embedder {
+    waker: Box<dyn EventLoopWaker>,
+}
+impl EmbedderMethods for Embedder {
+    fn create_event_loop_waker(&mut self) -> Box<dyn EventLoopWaker> {
+        self.waker.clone_box()
+    }
+}
+
+struct Window {
+    coordinates: RefCell<EmbedderCoordinates>,
+    animating: Cell<bool>,
+}
+impl WindowMethods for Window {
+    fn get_coordinates(&self) -> EmbedderCoordinates {
+        self.coordinates.borrow().clone()
+    }
+    fn set_animation_state(&self, state: AnimationState) {
+        self.animating.set(state == AnimationState::Animating);
+    }
+}
+
+/// The modifier that zoom (and other browser) shortcuts are used with.
+fn shortcut_modifier() -> Modifiers {
+    if cfg!(target_os = "macos") {
+        Modifiers::META
+    } else {
+        Modifiers::CONTROL
+    }
+}
+
+/// Servo's zoom events are relative to the current level, so jumping to a
+/// particular level means resetting first.
+fn push_zoom_events(level: f32, events: &mut Vec<EmbedderEvent>) {
+    events.push(EmbedderEvent::ResetZoom);
+    if level != 1.0 {
+        events.push(EmbedderEvent::Zoom(level));
+    }
+}
+
+fn paths_to_strings(paths: Vec<PathBuf>) -> Vec<String> {
+    paths
+        .into_iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect()
+}
+
+fn viewport_for_size(size: DeviceIntSize) -> DeviceIntRect {
+    let viewport_origin = DeviceIntPoint::zero(); // bottom left
+    DeviceIntRect::from_origin_and_size(viewport_origin, size)
+}
+
+/// A webview's session history, as Servo last reported it.
+struct History {
+    urls: Vec<ServoUrl>,
+    /// The index of the current entry in `urls`.
+    index: usize,
+}
+
+impl History {
+    fn current(&self) -> Option<&ServoUrl> {
+        self.urls.get(self.index)
+    }
+
+    fn can_go_back(&self) -> bool {
+        self.index > 0
+    }
+
+    fn can_go_forward(&self) -> bool {
+        self.index + 1 < self.urls.len()
+    }
+}
+
+/// Who asked for a menu, and how to answer them.
+enum MenuRequest {
+    Context {
+        sender: IpcSender<ContextMenuResult>,
+        /// What each item does, in the order they are shown.
+        actions: Vec<ContextMenuAction>,
+        target: ContextMenuTarget,
+    },
+    /// A `<select>` element's dropdown, with the id of the option behind
+    /// each item. Group labels have none.
+    Select {
+        sender: IpcSender<Option<usize>>,
+        option_ids: Vec<Option<usize>>,
+    },
+    /// Whether to let a devtools client connect.
+    DevtoolsConnection(IpcSender<bool>),
+}
+
+/// A menu waiting for the user to pick something.
+struct OpenMenu {
+    request: MenuRequest,
+    /// lepton's own rendering of the menu, when the backend has no native
+    /// menus.
+    overlay: Option<GlMenu>,
+}
+
+/// Where and how the page is drawn into the window.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CompositeOptions {
+    /// The part of the window to draw the page in, in device pixels. The
+    /// page keeps the window's size and is scaled to fit. `None` fills the
+    /// whole window.
+    pub dest: Option<DeviceRect>,
+    /// From 0 for invisible to 1 for opaque.
+    pub opacity: f32,
+    /// What to clear the window to before drawing the page, as RGBA. `None`
+    /// draws over whatever is already in the window.
+    pub clear_color: Option<[f32; 4]>,
+}
+
+impl Default for CompositeOptions {
+    fn default() -> CompositeOptions {
+        CompositeOptions {
+            dest: None,
+            opacity: 1.0,
+            clear_color: Some([1.0, 1.0, 1.0, 1.0]),
+        }
+    }
+}
+
+/// The window's own GL context, wrapped so surfman can bind Servo's surfaces
+/// to it, and the GL functions loaded for it.
+struct WindowContext {
+    context: surfman::Context,
+    gl: support::Gl,
+}
+
+pub struct App {
+    servo: Option<Servo>,
+    window: Rc<Window>,
+    rendering_context: Rc<SurfmanRenderingContext>,
+    window_context: Option<WindowContext>,
+    webview_id: WebViewId,
+    cursor_pos: DevicePoint,
+    zoom_levels: ZoomLevels,
+    touches: TouchTracker,
+    touch_mouse_emulation: bool,
+    /// Whether a composition has been started in the page and not ended.
+    composing: bool,
+    /// Each webview's session history.
+    histories: HashMap<WebViewId, History>,
+    /// The link the cursor is over.
+    hovered_link: Option<ServoUrl>,
+    /// The port Servo's devtools server listens on, once it has started.
+    devtools_port: Option<u16>,
+    /// The page's title, which the window's is made from.
+    page_title: Option<String>,
+    custom_menu_items: Vec<(String, ContextMenuCallback)>,
+    menu: Option<OpenMenu>,
+    /// Where to send the files picked for a file input, while its picker is
+    /// open.
+    file_request: Option<IpcSender<Option<Vec<String>>>>,
+    picked_files: Vec<PathBuf>,
+    composite: CompositeOptions,
+    painter: Option<Box<dyn Painter>>,
+    frame_timings: Option<FrameTimings>,
+    scheduler: FrameScheduler,
+    perf: PerfRecorder,
+    show_perf_hud: bool,
+    /// Events waiting to be handed to Servo together.
+    queue: EventQueue,
+    /// Where the input passed to Servo is being recorded, if anywhere.
+    recorder: Option<Recorder>,
+    /// The recording being replayed, until it runs out.
+    replay: Option<Replayer>,
+    screenshot_dir: Option<PathBuf>,
+    /// How many checkpoints have been reached, while recording or replaying.
+    checkpoints: u32,
+    /// A checkpoint was reached, and the next frame is to be saved.
+    screenshot_pending: bool,
+    /// Scripts run by the embedding application, waiting for their results.
+    scripts: ScriptEvaluator,
+    bridge: Bridge,
+}
+
+impl App {
+    pub fn new(backend: &dyn WindowBackend, config: Config) -> App {
+        // Initialize surfman
+        let connection = backend.connection();
+        let adapter = connection
+            .create_adapter()
+            .expect("Failed to create adapter");
+
+        let surface_size = backend.inner_size();
+        let rendering_context =
+            SurfmanRenderingContext::create(&connection, &adapter, Some(surface_size.to_untyped()))
+                .expect("Failed to create WR surfman");
+
+        let viewport = viewport_for_size(surface_size);
+        let hidpi_factor = backend.scale_factor();
+        let screen = backend.screen_geometry();
+        let window = Rc::new(Window {
+            animating: Cell::new(false),
+            coordinates: RefCell::new(EmbedderCoordinates {
+                hidpi_factor: Scale::new(hidpi_factor),
+                screen_size: device_size_to_independent(screen.screen_size, hidpi_factor),
+                available_screen_size: device_size_to_independent(
+                    screen.available_screen_size,
+                    hidpi_factor,
+                ),
+                window_rect: device_rect_to_independent(screen.window_rect, hidpi_factor),
+                framebuffer: viewport.size(),
+                viewport,
+            }),
+        });
+
+        let mut opts = Opts::default();
+        if cfg!(feature = "webdriver") {
+            // Servo runs the server, and carries out its commands itself.
+            opts.webdriver_port = config.webdriver_port;
+        }
+        let mut prefs = Preferences::default();
+        if config.transparent {
+            // What WebRender clears to, and so what shows through wherever
+            // the page has no background of its own.
+            prefs.shell_background_color_rgba = [0.0, 0.0, 0.0, 0.0];
+        }
+        if let Some(port) = config.devtools_port {
+            prefs.devtools_server_enabled = true;
+            prefs.devtools_server_port = port as i64;
+        }
+        let rendering_context = Rc::new(rendering_context);
+        let mut servo = Servo::new(
+            opts,
+            prefs,
+            rendering_context.clone(),
+            Box::new(Embedder {
+                waker: backend.create_event_loop_waker(),
+            }),
+            window.clone(),
+            None,
+            CompositeTarget::Window,
+        );
+        // Unless the embedding application installed lepton's logger, Servo's
+        // has to be in place before anything else is logged.
+        if !logging::is_installed() {
+            servo.setup_logging();
+        }
+        if let Some(port) = config.webdriver_port {
+            if cfg!(feature = "webdriver") {
+                // It listens on every interface, and takes no address to
+                // listen on instead.
+                log::warn!(
+                    "WebDriver server listening on 0.0.0.0:{}, reachable from other machines unless a firewall stops them",
+                    port
+                );
+            } else {
+                log::error!("Can't start a WebDriver server: lepton was built without the `webdriver` feature");
+            }
+        }
+        let replay = config.replay.as_deref().and_then(|path| match Replayer::open(path) {
+            Ok(replay) => Some(replay),
+            Err(error) => {
+                log::error!("Failed to open recording {}: {}", path.display(), error);
+                None
+            }
+        });
+        let url = match &replay {
+            Some(replay) => match ServoUrl::parse(&replay.header.url) {
+                Ok(url) => url,
+                Err(error) => {
+                    log::error!("Invalid URL {} in recording: {}", replay.header.url, error);
+                    config.url
+                }
+            },
+            None => config.url,
+        };
+        if let Some(replay) = &replay {
+            if replay.header.size() != surface_size || replay.header.scale_factor != hidpi_factor {
+                log::warn!(
+                    "Replaying in a {}x{} window at {}x, recorded at {}x{} at {}x",
+                    surface_size.width,
+                    surface_size.height,
+                    hidpi_factor,
+                    replay.header.width,
+                    replay.header.height,
+                    replay.header.scale_factor
+                );
+            }
+        }
+        let recorder = config.record.as_deref().and_then(|path| {
+            let header = Header {
+                url: url.to_string(),
+                width: surface_size.width,
+                height: surface_size.height,
+                scale_factor: hidpi_factor,
+            };
+            match Recorder::create(path, &header) {
+                Ok(recorder) => Some(recorder),
+                Err(error) => {
+                    log::warn!("Failed to start recording to {}: {}", path.display(), error);
+                    None
+                }
+            }
+        });
+
+        let webview_id = WebViewId::new();
+        servo.handle_events(vec![EmbedderEvent::NewWebView(url, webview_id)]);
+
+        let mut composite = CompositeOptions::default();
+        if config.transparent {
+            composite.clear_color = Some([0.0, 0.0, 0.0, 0.0]);
+        }
+
+        let window_context = backend.native_context().and_then(|native_context| {
+            let device = rendering_context.device();
+            let mut context = unsafe {
+                device
+                    .create_context_from_native_context(native_context)
+                    .unwrap()
+            };
+            match support::load(
+                |symbol| device.get_proc_address(&context, symbol),
+                config.gl_debug,
+            ) {
+                Ok(gl) => Some(WindowContext { context, gl }),
+                Err(error) => {
+                    log::error!(target: logging::GL, "Can't draw to the window: {}", error);
+                    let _ = device.destroy_context(&mut context);
+                    None
+                }
+            }
+        });
+
+        App {
+            servo: Some(servo),
+            window,
+            rendering_context,
+            window_context,
+            webview_id,
+            cursor_pos: DevicePoint::zero(),
+            zoom_levels: ZoomLevels::default(),
+            touches: TouchTracker::default(),
+            touch_mouse_emulation: config.touch_mouse_emulation,
+            composing: false,
+            histories: HashMap::new(),
+            hovered_link: None,
+            devtools_port: None,
+            page_title: None,
+            custom_menu_items: vec![],
+            menu: None,
+            file_request: None,
+            picked_files: config.picked_files,
+            composite,
+            painter: None,
+            frame_timings: None,
+            scheduler: FrameScheduler::new(config.max_fps, screen.refresh_rate),
+            perf: PerfRecorder::new(config.perf_csv),
+            show_perf_hud: config.perf_hud,
+            queue: EventQueue::new(config.coalesce_mouse_moves),
+            recorder,
+            replay,
+            screenshot_dir: config.screenshot_dir,
+            checkpoints: 0,
+            screenshot_pending: false,
+            scripts: ScriptEvaluator::new(backend.create_event_loop_waker()),
+            bridge: Bridge::new(config.bridge_origins),
+        }
+    }
+
+    /// Appends an item to every context menu from now on. `callback` runs
+    /// when it is chosen.
+    pub fn add_context_menu_item(
+        &mut self,
+        label: impl Into<String>,
+        callback: impl Fn(&mut App, &ContextMenuTarget) + 'static,
+    ) {
+        self.custom_menu_items.push((label.into(), Rc::new(callback)));
+    }
+
+    /// The webview lepton opened at startup.
+    pub fn webview_id(&self) -> WebViewId {
+        self.webview_id
+    }
+
+    /// Evaluates `source` in the page shown by `webview`, as a classic
+    /// script in its global scope, and calls `callback` with its value as
+    /// JSON once the page has run it.
+    pub fn evaluate_javascript(
+        &mut self,
+        webview: WebViewId,
+        source: &str,
+        callback: impl FnOnce(&mut App, JavaScriptResult) + 'static,
+    ) {
+        if self.servo.is_none() {
+            callback(self, Err(JavaScriptError::NoAnswer));
+            return;
+        }
+        let command = self.scripts.evaluate(source, Box::new(callback));
+        let browsing_context = BrowsingContextId::from(webview);
+        self.queue_events(vec![EmbedderEvent::WebDriverCommand(
+            WebDriverCommandMsg::ScriptCommand(browsing_context, command),
+        )]);
+    }
+
+    /// Gives pages from `origin`, e.g. `https://example.com`, the
+    /// `window.lepton` bridge from their next load on.
+    pub fn allow_bridge_origin(&mut self, origin: impl Into<String>) {
+        self.bridge.origins.push(origin.into());
+    }
+
+    /// Has `handler` called with every message pages send with
+    /// `lepton.postMessage`.
+    pub fn set_message_handler(&mut self, handler: impl Fn(&mut App, BridgeMessage) + 'static) {
+        self.bridge.handler = Some(Rc::new(handler));
+    }
+
+    /// Lets pages call `method` with `lepton.call(name, params)`. The
+    /// parameters are deserialized from the page's JSON, and the result, or
+    /// the error the promise is rejected with, serialized back.
+    pub fn add_rpc_method<P, R>(
+        &mut self,
+        name: impl Into<String>,
+        method: impl Fn(&mut App, P) -> Result<R, String> + 'static,
+    ) where
+        P: DeserializeOwned,
+        R: Serialize,
+    {
+        self.bridge.methods.insert(name.into(), bridge::rpc_method(method));
+    }
+
+    /// Sends `data` to the `message` listeners of `window.lepton` in the
+    /// page shown by `webview`, if it has the bridge.
+    pub fn post_message(&mut self, webview: WebViewId, data: &impl Serialize) {
+        let data = match serde_json::to_value(data) {
+            Ok(data) => data,
+            Err(error) => {
+                log::warn!("Can't send a message that isn't JSON: {}", error);
+                return;
+            }
+        };
+        let origin = match self.bridge_origin(webview) {
+            Some(origin) => origin,
+            None => {
+                log::debug!("Not sending a message to {:?}, which has no bridge", webview);
+                return;
+            }
+        };
+        let script = bridge::dispatch_script(&origin, &data);
+        self.evaluate_javascript(webview, &script, |_, result| {
+            if let Err(error) = result {
+                log::warn!("Failed to deliver a message to the page: {}", error);
+            }
+        });
+    }
+
+    /// Sends `data` to every page that has the bridge.
+    pub fn broadcast_message(&mut self, data: &impl Serialize) {
+        for webview in self.bridge.webviews.clone() {
+            self.post_message(webview, data);
+        }
+    }
+
+    /// Defines `window.lepton` in the page `webview` shows, if it is from
+    /// one of the allowed origins.
+    fn inject_bridge(&mut self, webview: WebViewId) {
+        if self.bridge.origins.is_empty() {
+            return;
+        }
+        let script = self.bridge.injection_script(webview);
+        self.evaluate_javascript(webview, &script, move |app, result| {
+            if result == Ok(Value::Bool(true)) && !app.bridge.webviews.contains(&webview) {
+                app.bridge.webviews.push(webview);
+            }
+        });
+    }
+
+    /// The origin of the page `webview` shows, if it is one of those allowed
+    /// the bridge. The page's own claims can't be trusted, so its origin is
+    /// taken from the history.
+    fn allowed_origin(&self, webview: WebViewId) -> Option<String> {
+        let url = self.histories.get(&webview)?.current()?;
+        let origin = url.origin().ascii_serialization();
+        self.bridge.allows(&origin).then_some(origin)
+    }
+
+    /// The origin of the page `webview` shows, if the bridge has been
+    /// defined in it and it is still from an allowed origin.
+    fn bridge_origin(&self, webview: WebViewId) -> Option<String> {
+        if !self.bridge.webviews.contains(&webview) {
+            return None;
+        }
+        self.allowed_origin(webview)
+    }
+
+    /// Handles what a page sent through the bridge, and returns the answer
+    /// to its prompt.
+    fn handle_bridge_prompt(
+        &mut self,
+        webview: WebViewId,
+        token: &str,
+        envelope: Result<Envelope, String>,
+    ) -> Value {
+        // Only the top-level document the bridge was defined in knows the
+        // token; frames, and documents from before a navigation elsewhere,
+        // don't.
+        if !self.bridge.is_token_of(webview, token) {
+            log::warn!("Ignoring a bridge message from a frame without the bridge in {:?}", webview);
+            return bridge::call_answer(Err("This page can't message lepton".to_owned()));
+        }
+        // The page may not be known to have the bridge yet, if it sends
+        // something while the bridge is still being defined.
+        let origin = match self.allowed_origin(webview) {
+            Some(origin) => origin,
+            None => {
+                log::warn!("Ignoring a bridge message from {:?}, whose page isn't allowed", webview);
+                return bridge::call_answer(Err("This page can't message lepton".to_owned()));
+            }
+        };
+        match envelope {
+            Ok(Envelope::Message { data }) => {
+                match self.bridge.handler.clone() {
+                    Some(handler) => handler(self, BridgeMessage { webview, origin, data }),
+                    None => log::debug!("No handler for the message from {}", origin),
+                }
+                Value::Null
+            }
+            Ok(Envelope::Call { method, params }) => {
+                let result = match self.bridge.methods.get(&method).cloned() {
+                    Some(rpc_method) => rpc_method(self, params),
+                    None => Err(format!("No method named {}", method)),
+                };
+                bridge::call_answer(result)
+            }
+            Err(error) => bridge::call_answer(Err(format!("Invalid bridge message: {}", error))),
+        }
+    }
+
+    fn run_script_callbacks(&mut self) {
+        for (callback, result) in self.scripts.take_finished() {
+            callback(self, result);
+        }
+    }
+
+    pub fn composite_options(&self) -> CompositeOptions {
+        self.composite
+    }
+
+    /// Changes where and how the page is drawn, from the next frame on.
+    pub fn set_composite_options(&mut self, options: CompositeOptions) {
+        self.composite = options;
+    }
+
+    /// Hands the drawing of the window over to `painter`, which gets the
+    /// page's frames as textures to place in a scene of its own. Composite
+    /// options no longer apply, and the positions in input events are taken
+    /// to be in the page already: the embedder maps them through its scene
+    /// before passing them on, for instance with
+    /// `page_point_from_texture_coords`.
+    pub fn set_painter(&mut self, painter: Box<dyn Painter>) {
+        self.painter = Some(painter);
+    }
+
+    /// How long drawing the last frame took, once one has been drawn to the
+    /// window.
+    pub fn last_frame_timings(&self) -> Option<FrameTimings> {
+        self.frame_timings
+    }
+
+    /// What became of the frames asked for so far.
+    pub fn frame_stats(&self) -> FrameStats {
+        self.scheduler.stats()
+    }
+
+    /// What became of the input passed on to Servo so far.
+    pub fn input_stats(&self) -> InputStats {
+        self.queue.stats()
+    }
+
+    /// The size of the page, which is that of its frames.
+    pub fn page_size(&self) -> DeviceIntSize {
+        self.window.coordinates.borrow().viewport.size()
+    }
+
+    /// Converts a point on a `FrameTexture`, in texture coordinates from 0
+    /// to 1 with the origin at the bottom left, to one in the page, as input
+    /// events expect.
+    pub fn page_point_from_texture_coords(&self, s: f32, t: f32) -> DevicePoint {
+        let size = self.page_size().to_f32();
+        DevicePoint::new(s * size.width, (1.0 - t) * size.height)
+    }
+
+    /// Converts a point in the window to one in the page, which differ when
+    /// the page isn't drawn over the whole window.
+    fn page_point(&self, point: DevicePoint) -> DevicePoint {
+        let Some(dest) = self.composite.dest.filter(|_| self.painter.is_none()) else {
+            return point;
+        };
+        let viewport = self.window.coordinates.borrow().viewport.size().to_f32();
+        DevicePoint::new(
+            (point.x - dest.min.x) * viewport.width / dest.width(),
+            (point.y - dest.min.y) * viewport.height / dest.height(),
+        )
+    }
+
+    /// Converts a rectangle in the page to one in the window.
+    fn window_rect(&self, rect: DeviceIntRect) -> DeviceIntRect {
+        let Some(dest) = self.composite.dest.filter(|_| self.painter.is_none()) else {
+            return rect;
+        };
+        let viewport = self.window.coordinates.borrow().viewport.size().to_f32();
+        let scale_x = dest.width() / viewport.width;
+        let scale_y = dest.height() / viewport.height;
+        let rect = rect.to_f32();
+        DeviceRect::new(
+            DevicePoint::new(dest.min.x + rect.min.x * scale_x, dest.min.y + rect.min.y * scale_y),
+            DevicePoint::new(dest.min.x + rect.max.x * scale_x, dest.min.y + rect.max.y * scale_y),
+        )
+        .round()
+        .to_i32()
+    }
+
+    /// The zoom level of the page currently shown, where 1.0 is unzoomed.
+    ///
+    /// This is page zoom, which lays the page out again at a different size.
+    /// Pinching magnifies the viewport instead, like on a phone: Servo's
+    /// compositor keeps track of that scale, and it is neither reflected
+    /// here nor remembered per origin.
+    pub fn zoom(&self) -> f32 {
+        self.zoom_levels.current()
+    }
+
+    /// Zooms the current page to `level`. The level is remembered for the
+    /// page's origin and restored when navigating back to it.
+    pub fn set_zoom(&mut self, level: f32) {
+        let mut events = vec![];
+        self.zoom_to(level, &mut events);
+        self.queue_events(events);
+    }
+
+    fn zoom_to(&mut self, level: f32, events: &mut Vec<EmbedderEvent>) {
+        let level = self.zoom_levels.set(level);
+        push_zoom_events(level, events);
+    }
+
+    /// Handles Ctrl (Cmd on macOS) with plus, minus and zero. Returns whether
+    /// the key was a zoom shortcut, in which case it must not reach the page.
+    fn handle_zoom_shortcut(&mut self, event: &KeyboardEvent, events: &mut Vec<EmbedderEvent>) -> bool {
+        if !event.modifiers.contains(shortcut_modifier()) {
+            return false;
+        }
+        let level = match &event.key {
+            Key::Character(c) if c == "+" || c == "=" => self.zoom_levels.stepped(1.0),
+            Key::Character(c) if c == "-" => self.zoom_levels.stepped(-1.0),
+            Key::Character(c) if c == "0" => 1.0,
+            _ => return false,
+        };
+        if event.state == KeyState::Down {
+            self.zoom_to(level, events);
+        }
+        true
+    }
+
+    /// Toggles the performance HUD on Ctrl+Shift+H (Cmd+Shift+H on macOS).
+    fn handle_perf_hud_shortcut(&mut self, event: &KeyboardEvent) -> bool {
+        let modifiers = shortcut_modifier() | Modifiers::SHIFT;
+        if !event.modifiers.contains(modifiers) {
+            return false;
+        }
+        if !matches!(&event.key, Key::Character(c) if c.eq_ignore_ascii_case("h")) {
+            return false;
+        }
+        if event.state == KeyState::Down {
+            self.show_perf_hud = !self.show_perf_hud;
+            self.scheduler.request_frame(false);
+        }
+        true
+    }
+
+    /// Marks a checkpoint in the recording on Ctrl+Shift+S (Cmd+Shift+S on
+    /// macOS), while recording.
+    fn handle_checkpoint_shortcut(&mut self, event: &KeyboardEvent) -> bool {
+        let modifiers = shortcut_modifier() | Modifiers::SHIFT;
+        if self.recorder.is_none() || !event.modifiers.contains(modifiers) {
+            return false;
+        }
+        if !matches!(&event.key, Key::Character(c) if c.eq_ignore_ascii_case("s")) {
+            return false;
+        }
+        if event.state == KeyState::Down {
+            self.checkpoint();
+        }
+        true
+    }
+
+    /// Records reaching a checkpoint, and has the next frame saved if
+    /// screenshots were asked for.
+    fn checkpoint(&mut self) {
+        self.checkpoints += 1;
+        log::info!("Checkpoint {}", self.checkpoints);
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(RecordedEvent::Checkpoint);
+        }
+        if self.screenshot_dir.is_some() {
+            self.screenshot_pending = true;
+            self.scheduler.request_frame(false);
+        }
+    }
+
+    fn record_resize(&mut self, size: DeviceIntSize) {
+        let scale_factor = self.window.coordinates.borrow().hidpi_factor.get();
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(RecordedEvent::Resize {
+                width: size.width,
+                height: size.height,
+                scale_factor,
+            });
+        }
+    }
+
+    /// Queues events for Servo, recording them first if asked to.
+    fn queue_events(&mut self, events: Vec<EmbedderEvent>) {
+        if let Some(recorder) = &mut self.recorder {
+            for event in events.iter().filter_map(RecordedEvent::from_embedder_event) {
+                recorder.record(event);
+            }
+        }
+        self.queue.extend(events);
+    }
+
+    /// Queues the replayed events whose time has come.
+    fn replay_due_events(&mut self, backend: &dyn WindowBackend) {
+        let Some(replay) = self.replay.as_mut() else {
+            return;
+        };
+        let due = replay.due_events();
+        if due.is_empty() {
+            return;
+        }
+        let finished = replay.is_finished();
+        for event in due {
+            let mut events = vec![];
+            match event {
+                RecordedEvent::Resize {
+                    width,
+                    height,
+                    scale_factor,
+                } => {
+                    let size = DeviceIntSize::new(width, height);
+                    backend.set_inner_size(size);
+                    self.window.coordinates.borrow_mut().hidpi_factor = Scale::new(scale_factor);
+                    self.scheduler.request_frame(false);
+                    self.resize(size, &mut events);
+                    self.record_resize(size);
+                }
+                RecordedEvent::Checkpoint => self.checkpoint(),
+                event => events.extend(event.to_embedder_event(self.webview_id)),
+            }
+            self.queue_events(events);
+        }
+        if finished {
+            log::info!("Replay finished");
+            self.replay = None;
+        }
+    }
+
+    /// Tells the event loop how long it may sleep, which is until the next
+    /// frame or replayed event is due, or a script times out.
+    fn poll(&mut self, backend: &dyn WindowBackend) -> LoopControl {
+        let control = self.scheduler.poll(backend, self.window.animating.get());
+        let deadlines = [
+            self.replay.as_ref().and_then(Replayer::next_deadline),
+            self.scripts.next_deadline(),
+        ];
+        match (control, deadlines.iter().flatten().min().copied()) {
+            (LoopControl::Wait, Some(deadline)) => LoopControl::WaitUntil(deadline),
+            (LoopControl::WaitUntil(due), Some(deadline)) => LoopControl::WaitUntil(due.min(deadline)),
+            (control, _) => control,
+        }
+    }
+
+    fn scroll(&mut self, delta: WheelDelta, phase: TouchEventType, events: &mut Vec<EmbedderEvent>) {
+        // Create wheel event before snapping to the major axis of movement
+        let position = self.page_point(self.cursor_pos);
+        events.push(EmbedderEvent::Wheel(delta, position));
+
+        // Scroll events snap to the major axis of movement, with vertical
+        // preferred over horizontal.
+        let (mut dx, mut dy) = (delta.x, delta.y);
+        if dy.abs() >= dx.abs() {
+            dx = 0.0;
+        } else {
+            dy = 0.0;
+        }
+        let scroll_location = ScrollLocation::Delta(LayoutVector2D::new(dx as f32, dy as f32));
+        events.push(EmbedderEvent::Scroll(
+            scroll_location,
+            position.to_i32(),
+            phase,
+        ));
+    }
+
+    fn handle_touch(
+        &mut self,
+        id: u64,
+        phase: TouchEventType,
+        position: DevicePoint,
+        events: &mut Vec<EmbedderEvent>,
+    ) {
+        let window_position = position;
+        let position = self.page_point(position);
+        let emulate_mouse = self.touch_mouse_emulation && self.touches.is_primary(id);
+        match phase {
+            TouchEventType::Down => {
+                let (index, cancelled) = self.touches.touch_down(id, position);
+                if self.touches.is_pinching() {
+                    // A second finger turns the gesture into a pinch, which
+                    // lepton handles itself rather than the page.
+                    match cancelled.first() {
+                        Some(&(_, primary_position)) if self.touch_mouse_emulation => {
+                            // Let go of the emulated button, without a click.
+                            events.push(EmbedderEvent::MouseWindowEventClass(
+                                MouseWindowEvent::MouseUp(MouseButton::Left, primary_position),
+                            ));
+                        }
+                        _ => {}
+                    }
+                    for (index, position) in cancelled {
+                        events.push(EmbedderEvent::Touch(TouchEventType::Cancel, TouchId(index), position));
+                    }
+                    return;
+                }
+                events.push(EmbedderEvent::Touch(phase, TouchId(index), position));
+                if self.touch_mouse_emulation && self.touches.is_primary(id) {
+                    self.cursor_pos = window_position;
+                    events.push(EmbedderEvent::MouseWindowMoveEventClass(position));
+                    events.push(EmbedderEvent::MouseWindowEventClass(
+                        MouseWindowEvent::MouseDown(MouseButton::Left, position),
+                    ));
+                }
+            }
+            TouchEventType::Move => {
+                // Fingers that went down before the window saw them are
+                // unknown to the page.
+                let Some(index) = self.touches.index(id) else {
+                    return;
+                };
+                if let Some(magnification) = self.touches.touch_move(id, position) {
+                    events.push(EmbedderEvent::PinchZoom(magnification));
+                } else if !self.touches.is_pinching() {
+                    events.push(EmbedderEvent::Touch(phase, TouchId(index), position));
+                    if emulate_mouse {
+                        self.cursor_pos = window_position;
+                        events.push(EmbedderEvent::MouseWindowMoveEventClass(position));
+                    }
+                }
+            }
+            TouchEventType::Up | TouchEventType::Cancel => {
+                let Some(index) = self.touches.index(id) else {
+                    return;
+                };
+                let was_pinching = self.touches.is_pinching();
+                self.touches.touch_up(id);
+                if was_pinching {
+                    return;
+                }
+                events.push(EmbedderEvent::Touch(phase, TouchId(index), position));
+                if emulate_mouse {
+                    events.push(EmbedderEvent::MouseWindowEventClass(
+                        MouseWindowEvent::MouseUp(MouseButton::Left, position),
+                    ));
+                    if phase == TouchEventType::Up {
+                        events.push(EmbedderEvent::MouseWindowEventClass(
+                            MouseWindowEvent::Click(MouseButton::Left, position),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Turns input method activity into the start/update/end sequence of
+    /// composition events the DOM expects.
+    fn handle_ime(&mut self, event: ImeEvent, events: &mut Vec<EmbedderEvent>) {
+        let mut composition = |state, data| {
+            events.push(EmbedderEvent::IMEComposition(CompositionEvent { state, data }));
+        };
+        match event {
+            ImeEvent::Preedit(text) => {
+                if text.is_empty() && !self.composing {
+                    return;
+                }
+                if !self.composing {
+                    composition(CompositionState::Start, String::new());
+                    self.composing = true;
+                }
+                composition(CompositionState::Update, text);
+            }
+            ImeEvent::Commit(text) => {
+                if !self.composing {
+                    composition(CompositionState::Start, String::new());
+                }
+                composition(CompositionState::End, text);
+                self.composing = false;
+            }
+            ImeEvent::Disabled => {
+                if self.composing {
+                    composition(CompositionState::End, String::new());
+                    self.composing = false;
+                }
+                events.push(EmbedderEvent::IMEDismissed);
+            }
+        }
+    }
+
+    /// Pops up a menu below `anchor`, natively if the backend can, or drawn
+    /// over the page otherwise.
+    fn open_menu(
+        &mut self,
+        backend: &dyn WindowBackend,
+        items: Vec<MenuItem>,
+        anchor: DeviceIntRect,
+        request: MenuRequest,
+        events: &mut Vec<EmbedderEvent>,
+    ) {
+        // Only one menu can be open at a time.
+        self.close_menu(backend, None, events);
+
+        let overlay = if backend.show_menu(anchor, &items) {
+            None
+        } else if self.window_context.is_some() {
+            let hidpi_factor = self.window.coordinates.borrow().hidpi_factor.get();
+            self.scheduler.request_frame(false);
+            Some(GlMenu::new(items, anchor, backend.inner_size(), hidpi_factor))
+        } else {
+            // There is nowhere to show the menu.
+            self.answer_menu(backend, request, None, events);
+            return;
+        };
+        self.menu = Some(OpenMenu { request, overlay });
+    }
+
+    fn show_context_menu(
+        &mut self,
+        backend: &dyn WindowBackend,
+        webview_id: WebViewId,
+        sender: IpcSender<ContextMenuResult>,
+        page_items: Vec<String>,
+        events: &mut Vec<EmbedderEvent>,
+    ) {
+        let history = self.histories.get(&webview_id);
+        let target = ContextMenuTarget {
+            webview_id,
+            page_url: history.and_then(History::current).cloned(),
+            link_url: self.hovered_link.clone(),
+        };
+        let can_go_back = history.is_some_and(History::can_go_back);
+        let can_go_forward = history.is_some_and(History::can_go_forward);
+        let mut items = vec![];
+        let mut actions = vec![];
+        let mut add = |label: &str, enabled: bool, action: ContextMenuAction| {
+            items.push(MenuItem {
+                enabled,
+                ..MenuItem::new(label)
+            });
+            actions.push(action);
+        };
+        for (index, label) in page_items.iter().enumerate() {
+            add(label, true, ContextMenuAction::Page(index));
+        }
+        add("Back", can_go_back, ContextMenuAction::Back);
+        add("Forward", can_go_forward, ContextMenuAction::Forward);
+        add("Reload", true, ContextMenuAction::Reload);
+        if target.link_url.is_some() {
+            add("Copy link", true, ContextMenuAction::CopyLink);
+            add("Open link in new view", true, ContextMenuAction::OpenLinkInNewWebView);
+        }
+        if self.devtools_port.is_some() {
+            add("Inspect", true, ContextMenuAction::Inspect);
+        }
+        for (label, callback) in &self.custom_menu_items {
+            add(label, true, ContextMenuAction::Custom(callback.clone()));
+        }
+
+        let anchor =
+            DeviceIntRect::from_origin_and_size(self.cursor_pos.to_i32(), DeviceIntSize::zero());
+        let request = MenuRequest::Context {
+            sender,
+            actions,
+            target,
+        };
+        self.open_menu(backend, items, anchor, request, events);
+    }
+
+    /// Shows the dropdown of a `<select>` element, whose box is `anchor`.
+    /// Servo reports it in device pixels relative to the webview.
+    fn show_select_menu(
+        &mut self,
+        backend: &dyn WindowBackend,
+        options: Vec<SelectElementOptionOrOptgroup>,
+        selected: Option<usize>,
+        anchor: DeviceIntRect,
+        sender: IpcSender<Option<usize>>,
+        events: &mut Vec<EmbedderEvent>,
+    ) {
+        let option_item = |option: &SelectElementOption, indent: &str| MenuItem {
+            label: format!("{}{}", indent, option.label),
+            enabled: !option.is_disabled,
+            checked: selected == Some(option.id),
+        };
+        let mut items = vec![];
+        let mut option_ids = vec![];
+        for option in options {
+            match option {
+                SelectElementOptionOrOptgroup::Option(option) => {
+                    items.push(option_item(&option, ""));
+                    option_ids.push(Some(option.id));
+                }
+                SelectElementOptionOrOptgroup::Optgroup { label, options } => {
+                    items.push(MenuItem {
+                        enabled: false,
+                        ..MenuItem::new(label)
+                    });
+                    option_ids.push(None);
+                    for option in options {
+                        items.push(option_item(&option, "  "));
+                        option_ids.push(Some(option.id));
+                    }
+                }
+            }
+        }
+        let request = MenuRequest::Select { sender, option_ids };
+        let anchor = self.window_rect(anchor);
+        self.open_menu(backend, items, anchor, request, events);
+    }
+
+    /// Closes the open menu, if any, and carries out the chosen item.
+    fn close_menu(
+        &mut self,
+        backend: &dyn WindowBackend,
+        choice: Option<usize>,
+        events: &mut Vec<EmbedderEvent>,
+    ) {
+        let Some(menu) = self.menu.take() else {
+            return;
+        };
+        if menu.overlay.is_some() {
+            self.scheduler.request_frame(false);
+        }
+        self.answer_menu(backend, menu.request, choice, events);
+    }
+
+    fn answer_menu(
+        &mut self,
+        backend: &dyn WindowBackend,
+        request: MenuRequest,
+        choice: Option<usize>,
+        events: &mut Vec<EmbedderEvent>,
+    ) {
+        let (sender, actions, target) = match request {
+            MenuRequest::Context {
+                sender,
+                actions,
+                target,
+            } => (sender, actions, target),
+            MenuRequest::Select { sender, option_ids } => {
+                // Dismissing the dropdown leaves the selection as it was.
+                let id = choice.and_then(|index| option_ids.get(index).copied().flatten());
+                let _ = sender.send(id);
+                return;
+            }
+            MenuRequest::DevtoolsConnection(sender) => {
+                // Only an explicit choice lets the client in.
+                let allowed = choice == Some(1);
+                if allowed {
+                    log::info!("Allowing a devtools connection");
+                } else {
+                    log::info!("Denying a devtools connection");
+                }
+                let _ = sender.send(allowed);
+                return;
+            }
+        };
+        let action = choice.and_then(|index| actions.into_iter().nth(index));
+        let result = match action {
+            Some(ContextMenuAction::Page(index)) => ContextMenuResult::Selected(index),
+            Some(_) => ContextMenuResult::Ignored,
+            None => ContextMenuResult::Dismissed,
+        };
+        let _ = sender.send(result);
+
+        match action {
+            None | Some(ContextMenuAction::Page(_)) => {}
+            Some(ContextMenuAction::Back) => {
+                log::info!(target: logging::NAVIGATION, "Going back");
+                events.push(EmbedderEvent::Navigation(target.webview_id, TraversalDirection::Back(1)));
+            }
+            Some(ContextMenuAction::Forward) => {
+                log::info!(target: logging::NAVIGATION, "Going forward");
+                events.push(EmbedderEvent::Navigation(
+                    target.webview_id,
+                    TraversalDirection::Forward(1),
+                ));
+            }
+            Some(ContextMenuAction::Reload) => {
+                log::info!(target: logging::NAVIGATION, "Reloading");
+                events.push(EmbedderEvent::Reload(target.webview_id));
+            }
+            Some(ContextMenuAction::CopyLink) => {
+                if let Some(link) = &target.link_url {
+                    backend.clipboard().set_text(link.as_str());
+                }
+            }
+            Some(ContextMenuAction::OpenLinkInNewWebView) => {
+                if let Some(link) = target.link_url {
+                    log::info!(target: logging::NAVIGATION, "Opening {} in a new view", link);
+                    events.push(EmbedderEvent::NewWebView(link, WebViewId::new()));
+                }
+            }
+            Some(ContextMenuAction::Inspect) => {
+                if let Some(port) = self.devtools_port {
+                    log::info!(
+                        "Devtools server listening on port {}; connect to it from about:debugging in Firefox",
+                        port
+                    );
+                }
+            }
+            Some(ContextMenuAction::Custom(callback)) => callback(self, &target),
+        }
+    }
+
+    /// Asks the user whether a devtools client may connect, with a menu in
+    /// the middle of the window. Without a way to ask, it is turned away.
+    fn ask_devtools_connection(
+        &mut self,
+        backend: &dyn WindowBackend,
+        sender: IpcSender<bool>,
+        events: &mut Vec<EmbedderEvent>,
+    ) {
+        let items = vec![
+            MenuItem {
+                enabled: false,
+                ..MenuItem::new("Allow a devtools client to connect?")
+            },
+            MenuItem::new("Allow"),
+            MenuItem::new("Deny"),
+        ];
+        let size = backend.inner_size();
+        let center = DeviceIntPoint::new(size.width / 2, size.height / 2);
+        let anchor = DeviceIntRect::from_origin_and_size(center, DeviceIntSize::zero());
+        let request = MenuRequest::DevtoolsConnection(sender);
+        self.open_menu(backend, items, anchor, request, events);
+    }
+
+    /// Shows the page's title in the window's, along with where to reach
+    /// the devtools server if it is running.
+    fn update_title(&self, backend: &dyn WindowBackend) {
+        let title = self.page_title.as_deref().unwrap_or("lepton");
+        match self.devtools_port {
+            Some(port) => backend.set_title(&format!("{} [devtools on port {}]", title, port)),
+            None => backend.set_title(title),
+        }
+    }
+
+    fn select_files(
+        &mut self,
+        backend: &dyn WindowBackend,
+        filters: Vec<FilterPattern>,
+        multiple: bool,
+        sender: IpcSender<Option<Vec<String>>>,
+    ) {
+        let extensions = accepted_extensions(&filters);
+        if !self.picked_files.is_empty() {
+            let files = scripted_selection(&self.picked_files, &extensions, multiple);
+            let _ = sender.send(files.map(paths_to_strings));
+            return;
+        }
+        // Only one picker can be open at a time.
+        if self.file_request.is_some() || !backend.pick_files(&extensions, multiple) {
+            let _ = sender.send(None);
+            return;
+        }
+        self.file_request = Some(sender);
+    }
+
+    /// Gives lepton's own menu, if one is open, first go at input. Returns
+    /// the event if the menu had no use for it.
+    fn handle_menu_input(
+        &mut self,
+        backend: &dyn WindowBackend,
+        event: BackendEvent,
+        events: &mut Vec<EmbedderEvent>,
+    ) -> Option<BackendEvent> {
+        let Some(overlay) = self.menu.as_mut().and_then(|menu| menu.overlay.as_mut()) else {
+            return Some(event);
+        };
+        let response = match &event {
+            BackendEvent::CursorMoved(position) => {
+                self.cursor_pos = *position;
+                overlay.mouse_moved(*position);
+                MenuResponse::Open
+            }
+            BackendEvent::MouseInput { pressed, .. } => {
+                overlay.mouse_input(self.cursor_pos, *pressed)
+            }
+            BackendEvent::Touch { phase, position, .. } => match phase {
+                TouchEventType::Down => {
+                    overlay.mouse_moved(*position);
+                    overlay.mouse_input(*position, true)
+                }
+                TouchEventType::Move => {
+                    overlay.mouse_moved(*position);
+                    MenuResponse::Open
+                }
+                TouchEventType::Up => overlay.mouse_input(*position, false),
+                TouchEventType::Cancel => MenuResponse::Open,
+            },
+            BackendEvent::Keyboard(event) if event.state == KeyState::Down => {
+                overlay.key_down(&event.key)
+            }
+            BackendEvent::Keyboard(_)
+            | BackendEvent::Wheel { .. }
+            | BackendEvent::PinchZoom(_)
+            | BackendEvent::Ime(_) => MenuResponse::Open,
+            BackendEvent::Resized(_)
+            | BackendEvent::ScaleFactorChanged { .. }
+            | BackendEvent::CloseRequested => {
+                self.close_menu(backend, None, events);
+                return Some(event);
+            }
+            _ => return Some(event),
+        };
+        self.scheduler.request_frame(false);
+        if let MenuResponse::Closed(choice) = response {
+            self.close_menu(backend, choice, events);
+        }
+        None
+    }
+
+    /// Handles one event from the backend. What it produced for Servo is
+    /// queued, and handed over with everything else queued once the backend
+    /// delivers `BackendEvent::EventsCleared`.
+    pub fn handle_event(&mut self, backend: &dyn WindowBackend, event: BackendEvent) -> LoopControl {
+        if self.servo.is_none() {
+            return LoopControl::Exit;
+        }
+        match &event {
+            BackendEvent::RedrawRequested
+            | BackendEvent::Wake
+            | BackendEvent::Timeout
+            | BackendEvent::EventsCleared => {}
+            BackendEvent::CursorMoved(_) => log::trace!(target: logging::INPUT, "{:?}", event),
+            _ => log::debug!(target: logging::INPUT, "{:?}", event),
+        }
+        self.run_script_callbacks();
+        self.replay_due_events(backend);
+        // The user's own input would throw a replay off.
+        if self.replay.is_some()
+            && matches!(
+                event,
+                BackendEvent::CursorMoved(_)
+                    | BackendEvent::MouseInput { .. }
+                    | BackendEvent::Wheel { .. }
+                    | BackendEvent::Touch { .. }
+                    | BackendEvent::PinchZoom(_)
+                    | BackendEvent::Keyboard(_)
+                    | BackendEvent::Ime(_)
+            )
+        {
+            return self.poll(backend);
+        }
+        let mut events = vec![];
+        let Some(event) = self.handle_menu_input(backend, event, &mut events) else {
+            self.queue_events(events);
+            return self.poll(backend);
+        };
+        match event {
+            BackendEvent::Resized(new_size) => {
+                self.scheduler.request_frame(false);
+                self.resize(new_size, &mut events);
+                self.record_resize(new_size);
+                self.update_screen_geometry(backend, &mut events);
+            }
+            BackendEvent::Moved => {
+                self.update_screen_geometry(backend, &mut events);
+            }
+            BackendEvent::ScaleFactorChanged { scale_factor, size } => {
+                let old_scale_factor = self.window.coordinates.borrow().hidpi_factor.get();
+                self.window.coordinates.borrow_mut().hidpi_factor = Scale::new(scale_factor);
+                // The cursor hasn't moved, but its position in device pixels has.
+                self.cursor_pos =
+                    rescale_device_point(self.cursor_pos, old_scale_factor, scale_factor);
+                // Servo picks up the new factor from the coordinates when it
+                // handles the resulting `WindowResize`.
+                self.scheduler.request_frame(false);
+                self.resize(size, &mut events);
+                self.record_resize(size);
+                self.update_screen_geometry(backend, &mut events);
+            }
+            BackendEvent::CloseRequested => {
+                events.push(EmbedderEvent::Quit);
+            }
+            BackendEvent::CursorMoved(position) => {
+                self.cursor_pos = position;
+                let position = self.page_point(position);
+                events.push(EmbedderEvent::MouseWindowMoveEventClass(position));
+            }
+            BackendEvent::MouseInput { button, pressed } => {
+                let position = self.page_point(self.cursor_pos);
+                if pressed {
+                    events.push(EmbedderEvent::MouseWindowEventClass(
+                        MouseWindowEvent::MouseDown(button, position),
+                    ));
+                } else {
+                    events.push(EmbedderEvent::MouseWindowEventClass(
+                        MouseWindowEvent::MouseUp(button, position),
+                    ));
+                    events.push(EmbedderEvent::MouseWindowEventClass(
+                        MouseWindowEvent::Click(button, position),
+                    ));
+                }
+            }
+            BackendEvent::Wheel { delta, phase, modifiers } => {
+                if modifiers.contains(shortcut_modifier()) {
+                    let notches = (delta.y / LINE_HEIGHT) as f32;
+                    self.zoom_to(self.zoom_levels.stepped(notches), &mut events);
+                } else {
+                    self.scroll(delta, phase, &mut events);
+                }
+            }
+            BackendEvent::Touch { id, phase, position } => {
+                self.handle_touch(id, phase, position, &mut events);
+            }
+            BackendEvent::PinchZoom(magnification) => {
+                // Viewport zoom, which `zoom_levels` doesn't track.
+                events.push(EmbedderEvent::PinchZoom(magnification));
+            }
+            BackendEvent::Keyboard(event) => {
+                if !self.handle_zoom_shortcut(&event, &mut events)
+                    && !self.handle_perf_hud_shortcut(&event)
+                    && !self.handle_checkpoint_shortcut(&event)
+                {
+                    events.push(EmbedderEvent::Keyboard(event));
+                }
+            }
+            BackendEvent::Ime(event) => {
+                self.handle_ime(event, &mut events);
+            }
+            BackendEvent::MenuClosed(choice) => {
+                self.close_menu(backend, choice, &mut events);
+            }
+            BackendEvent::FilesPicked(files) => {
+                if let Some(sender) = self.file_request.take() {
+                    let _ = sender.send(files.map(paths_to_strings));
+                }
+            }
+            BackendEvent::RedrawRequested => {
+                self.paint(backend);
+            }
+            BackendEvent::Wake => {
+                events.push(EmbedderEvent::Idle);
+            }
+            BackendEvent::Timeout => {
+                // Servo advances its animations whenever it handles events.
+                self.scheduler.animation_ticked();
+                events.push(EmbedderEvent::Idle);
+            }
+            BackendEvent::EventsCleared => {
+                if !self.queue.is_empty() {
+                    let events = self.queue.take();
+                    return self.handle_servo_events(backend, events);
+                }
+            }
+        }
+
+        self.queue_events(events);
+        self.poll(backend)
+    }
+
+    fn resize(&mut self, new_size: DeviceIntSize, events: &mut Vec<EmbedderEvent>) {
+        let viewport = viewport_for_size(new_size);
+        let mut coordinates = self.window.coordinates.borrow_mut();
+        coordinates.viewport = viewport;
+        coordinates.framebuffer = viewport.size();
+        self.rendering_context.resize(new_size.to_untyped()).unwrap();
+        events.push(EmbedderEvent::MoveResizeWebView(self.webview_id, viewport.to_f32()));
+        events.push(EmbedderEvent::WindowResize);
+    }
+
+    /// Refreshes the screen and window geometry exposed to content through
+    /// `window.screen` and `screenX`/`screenY`. Those are only read when
+    /// content asks for them, so a move needs no notification; a different
+    /// screen size affects media queries, which are only re-evaluated on
+    /// resize.
+    fn update_screen_geometry(&mut self, backend: &dyn WindowBackend, events: &mut Vec<EmbedderEvent>) {
+        let screen = backend.screen_geometry();
+        self.scheduler.set_refresh_rate(screen.refresh_rate);
+        let mut coordinates = self.window.coordinates.borrow_mut();
+        let hidpi_factor = coordinates.hidpi_factor.get();
+        let screen_size = device_size_to_independent(screen.screen_size, hidpi_factor);
+        let available_screen_size =
+            device_size_to_independent(screen.available_screen_size, hidpi_factor);
+        coordinates.window_rect = device_rect_to_independent(screen.window_rect, hidpi_factor);
+        if coordinates.screen_size != screen_size
+            || coordinates.available_screen_size != available_screen_size
+        {
+            coordinates.screen_size = screen_size;
+            coordinates.available_screen_size = available_screen_size;
+            if !events.iter().any(|event| matches!(event, EmbedderEvent::WindowResize)) {
+                events.push(EmbedderEvent::WindowResize);
+            }
+        }
+    }
+
+    fn handle_servo_events(
+        &mut self,
+        backend: &dyn WindowBackend,
+        mut events: Vec<EmbedderEvent>,
+    ) -> LoopControl {
+        loop {
+            // Pick up what was queued while handling Servo's messages, such
+            // as scripts evaluated from a message handler.
+            if !self.queue.is_empty() {
+                events.extend(self.queue.take());
+            }
+            let Some(servo) = self.servo.as_mut() else {
+                return LoopControl::Exit;
+            };
+            let mut shutting_down = false;
+            let start = Instant::now();
+            servo.handle_events(events.drain(..));
+            let servo_events = servo.get_events();
+            self.perf.events_handled(start.elapsed(), servo_events.len());
+            if servo_events.len() == 0 {
+                break;
+            }
+            for (webview_id, event) in servo_events {
+                if matches!(event, EmbedderMsg::EventDelivered(CompositorEventVariant::MouseMoveEvent)) {
+                    log::trace!(target: logging::EMBEDDER_MSG, "{:?}", (webview_id, &event));
+                } else {
+                    log::debug!(target: logging::EMBEDDER_MSG, "{:?}", (webview_id, &event));
+                }
+                match event {
+                    EmbedderMsg::ReadyToPresent(_) => {
+                        self.scheduler.request_frame(true);
+                    }
+                    EmbedderMsg::Shutdown => {
+                        shutting_down = true;
+                        break;
+                    }
+                    EmbedderMsg::HeadParsed => {
+                        self.inject_bridge(webview_id.unwrap_or(self.webview_id));
+                    }
+                    EmbedderMsg::Prompt(PromptDefinition::Input(message, _, sender), _) => {
+                        // Other prompts go unanswered, like every other kind.
+                        if let Some((token, envelope)) = bridge::parse_prompt(&message) {
+                            let webview = webview_id.unwrap_or(self.webview_id);
+                            let answer = self.handle_bridge_prompt(webview, token, envelope);
+                            bridge::reply(&sender, &answer);
+                        }
+                    }
+                    EmbedderMsg::WebViewClosed(closed_webview_id) => {
+                        self.bridge.forget(closed_webview_id);
+                        self.histories.remove(&closed_webview_id);
+                    }
+                    EmbedderMsg::LoadStart => {
+                        // The new document gets the bridge once its head has
+                        // been parsed, if it is from an allowed origin.
+                        let webview = webview_id.unwrap_or(self.webview_id);
+                        self.bridge.webviews.retain(|other| *other != webview);
+                    }
+                    EmbedderMsg::LoadComplete => {
+                        // In case the page had no head to parse.
+                        self.inject_bridge(webview_id.unwrap_or(self.webview_id));
+                        // Recordings keep time from the first load.
+                        if let Some(recorder) = &mut self.recorder {
+                            recorder.load_complete();
+                        }
+                        if let Some(replay) = &mut self.replay {
+                            replay.load_complete();
+                        }
+                    }
+                    EmbedderMsg::AllowNavigationRequest(id, url) => {
+                        log::debug!(target: logging::NAVIGATION, "Allowing navigation to {}", url);
+                        events.push(EmbedderEvent::AllowNavigationResponse(id, true));
+                    }
+                    EmbedderMsg::HistoryChanged(urls, current) => {
+                        if let Some(url) = urls.get(current) {
+                            log::info!(target: logging::NAVIGATION, "Now showing {}", url);
+                        }
+                        let origin = urls.get(current).map(|url| url.origin());
+                        let webview = webview_id.unwrap_or(self.webview_id);
+                        let allowed = origin
+                            .as_ref()
+                            .is_some_and(|origin| self.bridge.allows(&origin.ascii_serialization()));
+                        if !allowed {
+                            // Whatever is shown now mustn't get messages, or
+                            // send them with the token of the page before.
+                            self.bridge.forget(webview);
+                        }
+                        if let Some(level) = origin.and_then(|origin| self.zoom_levels.navigated_to(origin)) {
+                            push_zoom_events(level, &mut events);
+                        }
+                        self.histories.insert(webview, History { urls, index: current });
+                    }
+                    EmbedderMsg::Status(status) => {
+                        self.hovered_link = status.and_then(|url| ServoUrl::parse(&url).ok());
+                    }
+                    EmbedderMsg::ShowContextMenu(sender, _title, items) => {
+                        let webview = webview_id.unwrap_or(self.webview_id);
+                        self.show_context_menu(backend, webview, sender, items, &mut events);
+                    }
+                    EmbedderMsg::ShowSelectElementMenu(options, selected, anchor, sender) => {
+                        self.show_select_menu(
+                            backend,
+                            options,
+                            selected,
+                            anchor,
+                            sender,
+                            &mut events,
+                        );
+                    }
+                    EmbedderMsg::SelectFiles(filters, multiple, sender) => {
+                        self.select_files(backend, filters, multiple, sender);
+                    }
+                    EmbedderMsg::OnDevtoolsStarted(port, token) => {
+                        match port {
+                            Ok(port) => log::info!(
+                                "Devtools server listening on localhost:{} with connection token {}",
+                                port,
+                                token
+                            ),
+                            Err(()) => log::warn!("Failed to start the devtools server"),
+                        }
+                        self.devtools_port = port.ok();
+                        self.update_title(backend);
+                    }
+                    EmbedderMsg::RequestDevtoolsConnection(sender) => {
+                        self.ask_devtools_connection(backend, sender, &mut events);
+                    }
+                    EmbedderMsg::ChangePageTitle(title) => {
+                        self.page_title = title;
+                        self.update_title(backend);
+                    }
+                    EmbedderMsg::GetClipboardContents(sender) => {
+                        backend.clipboard().request_text(Box::new(move |text| {
+                            let _ = sender.send(text.unwrap_or_default());
+                        }));
+                    }
+                    EmbedderMsg::SetClipboardContents(text) => {
+                        backend.clipboard().set_text(&text);
+                    }
+                    EmbedderMsg::ClearClipboardContents => {
+                        backend.clipboard().clear();
+                    }
+                    EmbedderMsg::ShowIME(input_type, _, _, area) => {
+                        backend.show_ime(input_type, area);
+                    }
+                    EmbedderMsg::HideIME => {
+                        backend.hide_ime();
+                    }
+                    EmbedderMsg::SetCursor(cursor) => {
+                        backend.set_cursor(cursor);
+                    }
+                    EmbedderMsg::WebViewOpened(new_webview_id) => {
+                        let rect = self.window.get_coordinates().get_viewport().to_f32();
+                        events.push(EmbedderEvent::FocusWebView(new_webview_id));
+                        events.push(EmbedderEvent::MoveResizeWebView(new_webview_id, rect));
+                        events.push(EmbedderEvent::RaiseWebViewToTop(new_webview_id, true));
+                    }
+                    _ => {}
+                }
+            }
+
+            if shutting_down {
+                if let Some(mut window_context) = self.window_context.take() {
+                    let _ = self
+                        .rendering_context
+                        .device()
+                        .destroy_context(&mut window_context.context);
+                }
+                let servo = self.servo.take().unwrap();
+                servo.deinit();
+                self.perf.write_csv();
+                let stats = self.scheduler.stats();
+                log::info!(
+                    "Presented {} frames ({} duplicates, {} dropped), coalescing {} more requests",
+                    stats.presented,
+                    stats.duplicates,
+                    stats.dropped,
+                    stats.coalesced
+                );
+                return LoopControl::Exit;
+            }
+        }
+
+        self.poll(backend)
+    }
+
+    /// Draws the page into the window.
+    ///
+    /// Servo's front buffer is bound to a texture of the window's context
+    /// anew for every frame: a `SurfaceTexture` owns the surface it binds,
+    /// and the surface has to be back in Servo's swap chain before
+    /// `with_front_buffer` returns, so the binding can't be kept from one
+    /// frame to the next. A frame is skipped, with an error logged, when
+    /// the window's context can't be made current to bind it.
+    fn paint(&mut self, backend: &dyn WindowBackend) {
+        let Some(servo) = self.servo.as_mut() else {
+            return;
+        };
+        let mut timings = FrameTimings::default();
+        // Servo's front buffer already holds what it last rendered, unless
+        // it has rendered something since.
+        if self.scheduler.start_frame(self.window.animating.get()) {
+            let start = Instant::now();
+            servo.present();
+            timings.servo_present = start.elapsed();
+        }
+
+        let Some(window_context) = self.window_context.as_mut() else {
+            return;
+        };
+        let start = Instant::now();
+        backend.make_current();
+        timings.bind = start.elapsed();
+        let size = backend.inner_size();
+        let gl = &window_context.gl;
+        let context = &mut window_context.context;
+        let painter = &mut self.painter;
+        let quad = Quad {
+            dest: self
+                .composite
+                .dest
+                .unwrap_or_else(|| DeviceRect::from_size(size.to_f32()))
+                .to_untyped(),
+            opacity: self.composite.opacity,
+            flip_y: false,
+        };
+        let clear_color = self.composite.clear_color.filter(|_| painter.is_none());
+        let mut result = Ok(());
+        let mut bound = true;
+        self.rendering_context.with_front_buffer(|device, surface| {
+            let start = Instant::now();
+            // Binding and unbinding only fail when the window's context
+            // can't be made current, so find that out while the frame can
+            // still be skipped with the surface untouched.
+            if let Err(error) = device.make_context_current(context) {
+                log::error!(target: logging::GL, "Failed to make the window's context current: {:?}", error);
+                bound = false;
+                return surface;
+            }
+            let info = device.surface_info(&surface);
+            let texture = match device.create_surface_texture(context, surface) {
+                Ok(texture) => texture,
+                Err((error, surface)) => {
+                    log::error!(target: logging::GL, "Failed to bind Servo's front buffer: {:?}", error);
+                    bound = false;
+                    return surface;
+                }
+            };
+            let texture_id = device.surface_texture_object(&texture);
+            timings.bind += start.elapsed();
+
+            let start = Instant::now();
+            if let Some(color) = clear_color {
+                gl.clear(color);
+            }
+            match painter {
+                Some(painter) => {
+                    let frame = FrameTexture {
+                        id: texture_id,
+                        target: device.surface_gl_texture_target(),
+                        size: DeviceIntSize::from_untyped(info.size),
+                    };
+                    let paint_context = PaintContext {
+                        device,
+                        context: &*context,
+                        window_size: size,
+                    };
+                    painter.paint(&frame, &paint_context);
+                    // Whatever the painter drew must be done sampling the
+                    // surface before it goes back to Servo.
+                    gl.flush();
+                }
+                None => {
+                    result = gl.draw_texture(device, texture_id, info.size, &quad, size.to_untyped())
+                }
+            }
+            timings.draw = start.elapsed();
+
+            let start = Instant::now();
+            // The painter may have made another context current.
+            if painter.is_some() {
+                backend.make_current();
+            }
+            let surface = match device.destroy_surface_texture(context, texture) {
+                Ok(surface) => surface,
+                Err((error, texture)) => {
+                    // The surface has to go back to Servo, so try again
+                    // with the window's context made current.
+                    log::error!(target: logging::GL, "Failed to unbind Servo's front buffer: {:?}", error);
+                    backend.make_current();
+                    match device.destroy_surface_texture(context, texture) {
+                        Ok(surface) => surface,
+                        // The context was current a moment ago, and
+                        // `with_front_buffer` leaves no way to hand Servo
+                        // anything but its surface.
+                        Err((error, _)) => unreachable!("Lost Servo's front buffer: {:?}", error),
+                    }
+                }
+            };
+            timings.unbind = start.elapsed();
+            surface
+        });
+        // A frame that can't be bound is dropped; the next one may fare
+        // better.
+        if !bound {
+            return;
+        }
+        let start = Instant::now();
+        if let Some(overlay) = self.menu.as_ref().and_then(|menu| menu.overlay.as_ref()) {
+            let canvas = overlay.render();
+            let origin = overlay.rect().min.to_untyped();
+            result = result.and_then(|()| {
+                gl.draw_pixels(canvas.pixels(), canvas.size(), origin, size.to_untyped())
+            });
+        }
+        if self.show_perf_hud {
+            let hidpi_factor = self.window.coordinates.borrow().hidpi_factor.get();
+            let canvas = self.perf.render_hud(font_for_scale(hidpi_factor));
+            let margin = (8.0 * hidpi_factor) as i32;
+            let origin = Point2D::new(size.width - canvas.size().width - margin, margin);
+            result = result.and_then(|()| {
+                gl.draw_pixels(canvas.pixels(), canvas.size(), origin, size.to_untyped())
+            });
+        }
+        timings.draw += start.elapsed();
+        if result.is_ok() && std::mem::take(&mut self.screenshot_pending) {
+            if let Some(dir) = &self.screenshot_dir {
+                let path = dir.join(format!("checkpoint-{}.png", self.checkpoints));
+                let saved = gl
+                    .read_pixels(size.to_untyped())
+                    .map_err(|error| error.to_string())
+                    .and_then(|pixels| {
+                        recording::save_screenshot(&path, &pixels, size).map_err(|error| error.to_string())
+                    });
+                match saved {
+                    Ok(()) => log::info!("Saved {}", path.display()),
+                    Err(error) => log::warn!("Failed to save {}: {}", path.display(), error),
+                }
+            }
+        }
+        // A frame that failed to draw is dropped; the next one may fare
+        // better.
+        match result {
+            Ok(()) => {
+                let start = Instant::now();
+                backend.present();
+                timings.present = start.elapsed();
+                self.frame_timings = Some(timings);
+                self.perf.frame_presented(timings);
+            }
+            Err(error) => log::warn!(target: logging::GL, "Failed to draw frame: {}", error),
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! Translation of glutin's keyboard input into the DOM keyboard events Servo
+//! understands.
+//!
+//! glutin reports which key was pressed and the text it produced as separate
+//! events, so printable keys only become a `KeyboardEvent` once their
+//! `ReceivedCharacter` arrives. Shortcuts are the exception: with Ctrl or
+//! the logo key held there is no useful text, so the key is reported with the
+//! character it carries on a US layout instead.
+
+use glutin::event::{ElementState, KeyboardInput, ModifiersState, VirtualKeyCode};
+use servo::keyboard_types::{Key, KeyState, KeyboardEvent, Location, Modifiers};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+fn named_key(code: VirtualKeyCode) -> Option<Key> {
+    use VirtualKeyCode::*;
+    Some(match code {
+        Escape => Key::Escape,
+        F1 => Key::F1,
+        F2 => Key::F2,
+        F3 => Key::F3,
+        F4 => Key::F4,
+        F5 => Key::F5,
+        F6 => Key::F6,
+        F7 => Key::F7,
+        F8 => Key::F8,
+        F9 => Key::F9,
+        F10 => Key::F10,
+        F11 => Key::F11,
+        F12 => Key::F12,
+        Snapshot => Key::PrintScreen,
+        Scroll => Key::ScrollLock,
+        Pause => Key::Pause,
+        Insert => Key::Insert,
+        Home => Key::Home,
+        Delete => Key::Delete,
+        End => Key::End,
+        PageDown => Key::PageDown,
+        PageUp => Key::PageUp,
+        Left => Key::ArrowLeft,
+        Up => Key::ArrowUp,
+        Right => Key::ArrowRight,
+        Down => Key::ArrowDown,
+        Back => Key::Backspace,
+        Return | NumpadEnter => Key::Enter,
+        Tab => Key::Tab,
+        Capital => Key::CapsLock,
+        Numlock => Key::NumLock,
+        LAlt | RAlt => Key::Alt,
+        LControl | RControl => Key::Control,
+        LShift | RShift => Key::Shift,
+        LWin | RWin => Key::Meta,
+        Apps => Key::ContextMenu,
+        Copy => Key::Copy,
+        Paste => Key::Paste,
+        Cut => Key::Cut,
+        _ => return None,
+    })
+}
+
+fn us_layout_char(code: VirtualKeyCode) -> Option<char> {
+    use VirtualKeyCode::*;
+    Some(match code {
+        Key1 | Numpad1 => '1',
+        Key2 | Numpad2 => '2',
+        Key3 | Numpad3 => '3',
+        Key4 | Numpad4 => '4',
+        Key5 | Numpad5 => '5',
+        Key6 | Numpad6 => '6',
+        Key7 | Numpad7 => '7',
+        Key8 | Numpad8 => '8',
+        Key9 | Numpad9 => '9',
+        Key0 | Numpad0 => '0',
+        A => 'a',
+        B => 'b',
+        C => 'c',
+        D => 'd',
+        E => 'e',
+        F => 'f',
+        G => 'g',
+        H => 'h',
+        I => 'i',
+        J => 'j',
+        K => 'k',
+        L => 'l',
+        M => 'm',
+        N => 'n',
+        O => 'o',
+        P => 'p',
+        Q => 'q',
+        R => 'r',
+        S => 's',
+        T => 't',
+        U => 'u',
+        V => 'v',
+        W => 'w',
+        X => 'x',
+        Y => 'y',
+        Z => 'z',
+        Space => ' ',
+        Apostrophe => '\'',
+        Backslash => '\\',
+        Comma => ',',
+        Equals | NumpadEquals => '=',
+        Grave => '`',
+        LBracket => '[',
+        Minus | NumpadSubtract => '-',
+        Period | NumpadDecimal => '.',
+        Plus | NumpadAdd => '+',
+        RBracket => ']',
+        Semicolon => ';',
+        Slash | NumpadDivide => '/',
+        Asterisk | NumpadMultiply => '*',
+        _ => return None,
+    })
+}
+
+fn location(code: VirtualKeyCode) -> Location {
+    use VirtualKeyCode::*;
+    match code {
+        LAlt | LControl | LShift | LWin => Location::Left,
+        RAlt | RControl | RShift | RWin => Location::Right,
+        Numpad0 | Numpad1 | Numpad2 | Numpad3 | Numpad4 | Numpad5 | Numpad6 | Numpad7
+        | Numpad8 | Numpad9 | NumpadAdd | NumpadDivide | NumpadDecimal | NumpadComma
+        | NumpadEnter | NumpadEquals | NumpadMultiply | NumpadSubtract => Location::Numpad,
+        _ => Location::Standard,
+    }
+}
+
+pub fn modifiers(state: ModifiersState) -> Modifiers {
+    let mut modifiers = Modifiers::empty();
+    modifiers.set(Modifiers::CONTROL, state.ctrl());
+    modifiers.set(Modifiers::SHIFT, state.shift());
+    modifiers.set(Modifiers::ALT, state.alt());
+    modifiers.set(Modifiers::META, state.logo());
+    modifiers
+}
+
+fn is_shortcut(state: ModifiersState) -> bool {
+    state.ctrl() || state.logo()
+}
+
+/// Pairs up glutin's key and character events.
+#[derive(Default)]
+pub struct KeyboardState {
+    modifiers: Cell<ModifiersState>,
+    /// The key each currently pressed scancode was reported as, so the
+    /// release can be reported as the same key.
+    pressed: RefCell<HashMap<u32, (Key, Location)>>,
+    /// A printable key that was pressed, waiting for its character.
+    pending: Cell<Option<(u32, Location)>>,
+}
+
+impl KeyboardState {
+    pub fn modifiers(&self) -> Modifiers {
+        modifiers(self.modifiers.get())
+    }
+
+    pub fn modifiers_changed(&self, state: ModifiersState) {
+        self.modifiers.set(state);
+    }
+
+    fn event(&self, state: KeyState, key: Key, location: Location, repeat: bool) -> KeyboardEvent {
+        KeyboardEvent {
+            state,
+            key,
+            location,
+            modifiers: self.modifiers(),
+            repeat,
+            ..Default::default()
+        }
+    }
+
+    pub fn key_input(&self, input: KeyboardInput) -> Option<KeyboardEvent> {
+        let code = input.virtual_keycode;
+        match input.state {
+            ElementState::Pressed => {
+                let location = code.map_or(Location::Standard, location);
+                let key = match code.and_then(named_key) {
+                    Some(key) => key,
+                    None if is_shortcut(self.modifiers.get()) => {
+                        Key::Character(code.and_then(us_layout_char)?.to_string())
+                    }
+                    None => {
+                        self.pending.set(Some((input.scancode, location)));
+                        return None;
+                    }
+                };
+                let repeat = self
+                    .pressed
+                    .borrow_mut()
+                    .insert(input.scancode, (key.clone(), location))
+                    .is_some();
+                Some(self.event(KeyState::Down, key, location, repeat))
+            }
+            ElementState::Released => {
+                let (key, location) = self.pressed.borrow_mut().remove(&input.scancode)?;
+                Some(self.event(KeyState::Up, key, location, false))
+            }
+        }
+    }
+
+    pub fn received_character(&self, character: char) -> Option<KeyboardEvent> {
+        if character.is_control() || is_shortcut(self.modifiers.get()) {
+            return None;
+        }
+        let key = Key::Character(character.to_string());
+        let location = match self.pending.take() {
+            Some((scancode, location)) => {
+                self.pressed
+                    .borrow_mut()
+                    .insert(scancode, (key.clone(), location));
+                location
+            }
+            None => Location::Standard,
+        };
+        Some(self.event(KeyState::Down, key, location, false))
+    }
+}
@@ -0,0 +1,332 @@
+use glutin::dpi::{PhysicalPosition, PhysicalSize};
+use glutin::event::{
+    ElementState, Event, Ime, MouseButton, MouseScrollDelta, StartCause, TouchPhase, WindowEvent,
+};
+use glutin::event_loop::{ControlFlow, EventLoop, EventLoopBuilder, EventLoopProxy};
+use glutin::platform::ContextTraitExt;
+use glutin::window::{CursorIcon, WindowBuilder};
+use glutin::{ContextBuilder, PossiblyCurrent, WindowedContext};
+use raw_window_handle::HasRawDisplayHandle;
+use servo::webrender_api::units::{DeviceIntPoint, DeviceIntRect, DeviceIntSize, DevicePoint};
+use servo::script_traits::{TouchEventType, WheelDelta, WheelMode};
+use servo::MouseButton as ServoMouseButton;
+use servo::{Cursor, EventLoopWaker, InputMethodType};
+use std::cell::RefCell;
+
+use crate::clipboard::{Clipboard, SystemClipboard};
+use crate::logging;
+use crate::menu::MenuItem;
+
+use super::glutin_keys::KeyboardState;
+use super::{
+    BackendEvent, ImeEvent, LoopControl, ScreenGeometry, WindowBackend, WindowOptions, LINE_HEIGHT,
+};
+
+fn glutin_size_to_euclid_size(size: PhysicalSize<u32>) -> DeviceIntSize {
+    DeviceIntSize::new(size.width as i32, size.height as i32)
+}
+
+fn glutin_position_to_euclid_point(position: PhysicalPosition<f64>) -> DevicePoint {
+    DevicePoint::new(position.x as f32, position.y as f32)
+}
+
+fn glutin_phase_to_touch_event_type(phase: TouchPhase) -> TouchEventType {
+    match phase {
+        TouchPhase::Started => TouchEventType::Down,
+        TouchPhase::Moved => TouchEventType::Move,
+        TouchPhase::Ended => TouchEventType::Up,
+        TouchPhase::Cancelled => TouchEventType::Cancel,
+    }
+}
+
+struct Waker(EventLoopProxy<BackendEvent>);
+impl EventLoopWaker for Waker {
+    fn clone_box(&self) -> Box<dyn EventLoopWaker> {
+        Box::new(Waker(self.0.clone()))
+    }
+    fn wake(&self) {
+        let _ = self.0.send_event(BackendEvent::Wake);
+    }
+}
+
+pub struct GlutinBackend {
+    // Events that don't come from the window (Servo waking the loop up, file
+    // picker results) arrive as user events.
+    event_loop: RefCell<Option<EventLoop<BackendEvent>>>,
+    proxy: EventLoopProxy<BackendEvent>,
+    // Making a glutin context current consumes it, so it has to be moved
+    // out and back in whenever that happens.
+    windowed_context: RefCell<Option<WindowedContext<PossiblyCurrent>>>,
+    keyboard: KeyboardState,
+    clipboard: SystemClipboard,
+}
+
+impl GlutinBackend {
+    pub fn new(title: &str) -> GlutinBackend {
+        GlutinBackend::with_options(title, &WindowOptions::default())
+    }
+
+    pub fn with_options(title: &str, options: &WindowOptions) -> GlutinBackend {
+        let el = EventLoopBuilder::with_user_event().build();
+        let proxy = el.create_proxy();
+        let wb = WindowBuilder::new()
+            .with_title(title)
+            .with_transparent(options.transparent);
+
+        let mut cb = ContextBuilder::new();
+        if options.transparent {
+            cb = cb.with_pixel_format(24, 8);
+        }
+        let windowed_context = cb.build_windowed(wb, &el).unwrap();
+        let windowed_context = unsafe { windowed_context.make_current().unwrap() };
+
+        log::debug!(
+            target: logging::GL,
+            "Pixel format of the window's GL context: {:?}",
+            windowed_context.get_pixel_format()
+        );
+
+        GlutinBackend {
+            event_loop: RefCell::new(Some(el)),
+            proxy,
+            windowed_context: RefCell::new(Some(windowed_context)),
+            keyboard: KeyboardState::default(),
+            clipboard: SystemClipboard::new(),
+        }
+    }
+
+    fn with_context<R>(&self, f: impl FnOnce(&WindowedContext<PossiblyCurrent>) -> R) -> R {
+        f(self.windowed_context.borrow().as_ref().unwrap())
+    }
+
+    fn translate_window_event(&self, event: WindowEvent) -> Option<BackendEvent> {
+        match event {
+            WindowEvent::Resized(physical_size) => {
+                if physical_size.width as i32 > 0 && physical_size.height as i32 > 0 {
+                    self.with_context(|context| context.resize(physical_size));
+                    Some(BackendEvent::Resized(glutin_size_to_euclid_size(physical_size)))
+                } else {
+                    None
+                }
+            }
+            WindowEvent::ScaleFactorChanged { scale_factor, new_inner_size } => {
+                self.with_context(|context| context.resize(*new_inner_size));
+                Some(BackendEvent::ScaleFactorChanged {
+                    scale_factor: scale_factor as f32,
+                    size: glutin_size_to_euclid_size(*new_inner_size),
+                })
+            }
+            WindowEvent::Moved(_) => Some(BackendEvent::Moved),
+            WindowEvent::CloseRequested => Some(BackendEvent::CloseRequested),
+            WindowEvent::CursorMoved { position, .. } => {
+                Some(BackendEvent::CursorMoved(glutin_position_to_euclid_point(position)))
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    MouseButton::Left => ServoMouseButton::Left,
+                    MouseButton::Middle => ServoMouseButton::Middle,
+                    MouseButton::Right => ServoMouseButton::Right,
+                    MouseButton::Other(_) => return None,
+                };
+                Some(BackendEvent::MouseInput {
+                    button,
+                    pressed: state == ElementState::Pressed,
+                })
+            }
+            WindowEvent::Touch(touch) => Some(BackendEvent::Touch {
+                id: touch.id,
+                phase: glutin_phase_to_touch_event_type(touch.phase),
+                position: glutin_position_to_euclid_point(touch.location),
+            }),
+            WindowEvent::MouseWheel { delta, phase, .. } => {
+                let (x, y, mode) = match delta {
+                    MouseScrollDelta::LineDelta(dx, dy) => (
+                        dx as f64 * LINE_HEIGHT,
+                        dy as f64 * LINE_HEIGHT,
+                        WheelMode::DeltaLine,
+                    ),
+                    MouseScrollDelta::PixelDelta(position) => {
+                        (position.x, position.y, WheelMode::DeltaPixel)
+                    }
+                };
+                Some(BackendEvent::Wheel {
+                    delta: WheelDelta { x, y, z: 0.0, mode },
+                    phase: glutin_phase_to_touch_event_type(phase),
+                    modifiers: self.keyboard.modifiers(),
+                })
+            }
+            WindowEvent::ModifiersChanged(state) => {
+                self.keyboard.modifiers_changed(state);
+                None
+            }
+            WindowEvent::KeyboardInput { input, .. } => {
+                self.keyboard.key_input(input).map(BackendEvent::Keyboard)
+            }
+            WindowEvent::ReceivedCharacter(character) => self
+                .keyboard
+                .received_character(character)
+                .map(BackendEvent::Keyboard),
+            WindowEvent::Ime(ime) => match ime {
+                Ime::Enabled => None,
+                Ime::Preedit(text, _) => Some(BackendEvent::Ime(ImeEvent::Preedit(text))),
+                Ime::Commit(text) => Some(BackendEvent::Ime(ImeEvent::Commit(text))),
+                Ime::Disabled => Some(BackendEvent::Ime(ImeEvent::Disabled)),
+            },
+            _ => None,
+        }
+    }
+}
+
+impl WindowBackend for GlutinBackend {
+    fn native_context(&self) -> Option<surfman::NativeContext> {
+        self.with_context(|context| unsafe {
+            Some(surfman::NativeContext(context.context().raw_handle()))
+        })
+    }
+
+    fn connection(&self) -> surfman::Connection {
+        let display_handle = self.with_context(|context| context.window().raw_display_handle());
+        surfman::Connection::from_raw_display_handle(display_handle)
+            .expect("Failed to create connection")
+    }
+
+    fn inner_size(&self) -> DeviceIntSize {
+        self.with_context(|context| glutin_size_to_euclid_size(context.window().inner_size()))
+    }
+
+    fn screen_geometry(&self) -> ScreenGeometry {
+        self.with_context(|context| {
+            let window = context.window();
+            let origin = window
+                .outer_position()
+                .map(|position| DeviceIntPoint::new(position.x, position.y))
+                .unwrap_or_else(|_| DeviceIntPoint::zero());
+            let window_rect = DeviceIntRect::from_origin_and_size(
+                origin,
+                glutin_size_to_euclid_size(window.outer_size()),
+            );
+            let monitor = window.current_monitor();
+            let screen_size = monitor
+                .as_ref()
+                .map(|monitor| glutin_size_to_euclid_size(monitor.size()))
+                .unwrap_or_else(|| window_rect.size());
+            ScreenGeometry {
+                screen_size,
+                // winit has no way to ask for the monitor's work area.
+                available_screen_size: screen_size,
+                window_rect,
+                refresh_rate: monitor
+                    .and_then(|monitor| monitor.refresh_rate_millihertz())
+                    .map(|millihertz| millihertz as f32 / 1000.0),
+            }
+        })
+    }
+
+    fn scale_factor(&self) -> f32 {
+        self.with_context(|context| context.window().scale_factor() as f32)
+    }
+
+    fn make_current(&self) {
+        // Switching contexts isn't free, and the window's is still current
+        // whenever Servo hasn't rendered since the last frame.
+        if self.with_context(|context| context.is_current()) {
+            return;
+        }
+        let context = self.windowed_context.borrow_mut().take().unwrap();
+        let context = unsafe { context.make_current() }.unwrap();
+        *self.windowed_context.borrow_mut() = Some(context);
+    }
+
+    fn present(&self) {
+        self.with_context(|context| context.swap_buffers().unwrap());
+    }
+
+    fn set_cursor(&self, cursor: Cursor) {
+        if let Some(cursor) = match cursor {
+            Cursor::None => Some(CursorIcon::Default),
+            Cursor::Pointer => Some(CursorIcon::Hand),
+            Cursor::Text => Some(CursorIcon::Text),
+            _ => None,
+        } {
+            self.with_context(|context| context.window().set_cursor_icon(cursor));
+        }
+    }
+
+    fn set_title(&self, title: &str) {
+        self.with_context(|context| context.window().set_title(title));
+    }
+
+    fn clipboard(&self) -> &dyn Clipboard {
+        &self.clipboard
+    }
+
+    fn show_ime(&self, _input_type: InputMethodType, area: DeviceIntRect) {
+        self.with_context(|context| {
+            let window = context.window();
+            window.set_ime_allowed(true);
+            window.set_ime_position(PhysicalPosition::new(area.min.x, area.max.y));
+        });
+    }
+
+    fn hide_ime(&self) {
+        self.with_context(|context| context.window().set_ime_allowed(false));
+    }
+
+    fn show_menu(&self, _anchor: DeviceIntRect, _items: &[MenuItem]) -> bool {
+        // winit has no menus; lepton draws its own in the window.
+        false
+    }
+
+    fn pick_files(&self, extensions: &[String], multiple: bool) -> bool {
+        // rfd shows the system's open panel, which blocks until it is closed
+        // and has to run on the main thread.
+        let mut dialog = rfd::FileDialog::new();
+        if !extensions.is_empty() {
+            dialog = dialog.add_filter("Accepted files", extensions);
+        }
+        let files = if multiple {
+            dialog.pick_files()
+        } else {
+            dialog.pick_file().map(|file| vec![file])
+        };
+        let _ = self.proxy.send_event(BackendEvent::FilesPicked(files));
+        true
+    }
+
+    fn request_redraw(&self) {
+        self.with_context(|context| context.window().request_redraw());
+    }
+
+    fn create_event_loop_waker(&self) -> Box<dyn EventLoopWaker> {
+        Box::new(Waker(self.proxy.clone()))
+    }
+
+    fn run<H>(self, mut handler: H)
+    where
+        H: FnMut(&dyn WindowBackend, BackendEvent) -> LoopControl + 'static,
+    {
+        let el = self.event_loop.borrow_mut().take().unwrap();
+        el.run(move |event, _, control_flow| {
+            let event = match event {
+                Event::LoopDestroyed => return,
+                Event::WindowEvent { event, .. } => self.translate_window_event(event),
+                Event::RedrawRequested(_) => Some(BackendEvent::RedrawRequested),
+                Event::MainEventsCleared => Some(BackendEvent::EventsCleared),
+                Event::NewEvents(StartCause::ResumeTimeReached { .. }) => {
+                    Some(BackendEvent::Timeout)
+                }
+                Event::UserEvent(event) => Some(event),
+                _ => None,
+            };
+            let Some(event) = event else {
+                return;
+            };
+            *control_flow = match handler(&self, event) {
+                LoopControl::Wait => ControlFlow::Wait,
+                LoopControl::WaitUntil(deadline) => ControlFlow::WaitUntil(deadline),
+                LoopControl::Poll => ControlFlow::Poll,
+                LoopControl::Exit => ControlFlow::Exit,
+            };
+        });
+    }
+}
@@ -0,0 +1,93 @@
+//! Translation of GDK key events into the DOM keyboard events Servo
+//! understands.
+
+use gtk::gdk;
+use gtk::gdk::keys::constants as keys;
+use servo::keyboard_types::{Key, KeyState, KeyboardEvent, Location, Modifiers};
+
+fn named_key(keyval: gdk::keys::Key) -> Option<Key> {
+    Some(match keyval {
+        keys::Escape => Key::Escape,
+        keys::F1 => Key::F1,
+        keys::F2 => Key::F2,
+        keys::F3 => Key::F3,
+        keys::F4 => Key::F4,
+        keys::F5 => Key::F5,
+        keys::F6 => Key::F6,
+        keys::F7 => Key::F7,
+        keys::F8 => Key::F8,
+        keys::F9 => Key::F9,
+        keys::F10 => Key::F10,
+        keys::F11 => Key::F11,
+        keys::F12 => Key::F12,
+        keys::Print => Key::PrintScreen,
+        keys::Scroll_Lock => Key::ScrollLock,
+        keys::Pause => Key::Pause,
+        keys::Insert | keys::KP_Insert => Key::Insert,
+        keys::Home | keys::KP_Home => Key::Home,
+        keys::Delete | keys::KP_Delete => Key::Delete,
+        keys::End | keys::KP_End => Key::End,
+        keys::Page_Down | keys::KP_Page_Down => Key::PageDown,
+        keys::Page_Up | keys::KP_Page_Up => Key::PageUp,
+        keys::Left | keys::KP_Left => Key::ArrowLeft,
+        keys::Up | keys::KP_Up => Key::ArrowUp,
+        keys::Right | keys::KP_Right => Key::ArrowRight,
+        keys::Down | keys::KP_Down => Key::ArrowDown,
+        keys::BackSpace => Key::Backspace,
+        keys::Return | keys::KP_Enter => Key::Enter,
+        keys::Tab | keys::ISO_Left_Tab => Key::Tab,
+        keys::Caps_Lock => Key::CapsLock,
+        keys::Num_Lock => Key::NumLock,
+        keys::Alt_L | keys::Alt_R => Key::Alt,
+        keys::Control_L | keys::Control_R => Key::Control,
+        keys::Shift_L | keys::Shift_R => Key::Shift,
+        keys::Super_L | keys::Super_R | keys::Meta_L | keys::Meta_R => Key::Meta,
+        keys::Menu => Key::ContextMenu,
+        _ => return None,
+    })
+}
+
+fn location(keyval: gdk::keys::Key) -> Location {
+    match keyval {
+        keys::Alt_L | keys::Control_L | keys::Shift_L | keys::Super_L | keys::Meta_L => {
+            Location::Left
+        }
+        keys::Alt_R | keys::Control_R | keys::Shift_R | keys::Super_R | keys::Meta_R => {
+            Location::Right
+        }
+        keyval if keyval.name().map_or(false, |name| name.starts_with("KP_")) => {
+            Location::Numpad
+        }
+        _ => Location::Standard,
+    }
+}
+
+pub fn modifiers(state: gdk::ModifierType) -> Modifiers {
+    let mut modifiers = Modifiers::empty();
+    modifiers.set(Modifiers::CONTROL, state.contains(gdk::ModifierType::CONTROL_MASK));
+    modifiers.set(Modifiers::SHIFT, state.contains(gdk::ModifierType::SHIFT_MASK));
+    modifiers.set(Modifiers::ALT, state.contains(gdk::ModifierType::MOD1_MASK));
+    modifiers.set(
+        Modifiers::META,
+        state.intersects(gdk::ModifierType::META_MASK | gdk::ModifierType::SUPER_MASK),
+    );
+    modifiers
+}
+
+pub fn keyboard_event(event: &gdk::EventKey, state: KeyState) -> Option<KeyboardEvent> {
+    let keyval = event.keyval();
+    let key = match named_key(keyval) {
+        Some(key) => key,
+        None => match keyval.to_unicode() {
+            Some(character) if !character.is_control() => Key::Character(character.to_string()),
+            _ => return None,
+        },
+    };
+    Some(KeyboardEvent {
+        state,
+        key,
+        location: location(keyval),
+        modifiers: modifiers(event.state()),
+        ..Default::default()
+    })
+}
@@ -0,0 +1,648 @@
+use gtk::prelude::*;
+use gtk::{gdk, glib};
+use servo::keyboard_types::KeyState;
+use servo::script_traits::{TouchEventType, WheelDelta, WheelMode};
+use servo::webrender_api::units::{DeviceIntRect, DeviceIntSize, DevicePoint};
+use servo::{Cursor, EventLoopWaker, InputMethodType, MouseButton};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Instant;
+
+use crate::clipboard::Clipboard;
+use crate::coordinates::{logical_point_to_device, logical_size_to_device, LogicalToDevice};
+use crate::menu::MenuItem;
+
+use super::gtk_keys::{keyboard_event, modifiers};
+use super::{
+    BackendEvent, ImeEvent, LoopControl, ScreenGeometry, WindowBackend, WindowOptions, LINE_HEIGHT,
+};
+
+struct Waker(async_channel::Sender<BackendEvent>);
+impl EventLoopWaker for Waker {
+    fn clone_box(&self) -> Box<dyn EventLoopWaker> {
+        Box::new(Waker(self.0.clone()))
+    }
+    fn wake(&self) {
+        let _ = self.0.try_send(BackendEvent::Wake);
+    }
+}
+
+fn input_purpose(input_type: InputMethodType) -> gtk::InputPurpose {
+    match input_type {
+        InputMethodType::Email => gtk::InputPurpose::Email,
+        InputMethodType::Number => gtk::InputPurpose::Number,
+        InputMethodType::Password => gtk::InputPurpose::Password,
+        InputMethodType::Tel => gtk::InputPurpose::Phone,
+        InputMethodType::Url => gtk::InputPurpose::Url,
+        _ => gtk::InputPurpose::FreeForm,
+    }
+}
+
+fn gdk_button_to_servo_button(button: u32) -> Option<MouseButton> {
+    match button {
+        1 => Some(MouseButton::Left),
+        2 => Some(MouseButton::Middle),
+        3 => Some(MouseButton::Right),
+        _ => None,
+    }
+}
+
+/// The desktop clipboard, through GTK.
+///
+/// Text is read asynchronously: waiting for it would run a nested main loop,
+/// in which GTK could call back into the `App` while it is busy.
+struct GtkClipboard {
+    clipboard: gtk::Clipboard,
+}
+
+impl Clipboard for GtkClipboard {
+    fn request_text(&self, callback: Box<dyn FnOnce(Option<String>)>) {
+        self.clipboard
+            .request_text(move |_, text| callback(text.map(str::to_owned)));
+    }
+
+    fn set_text(&self, text: &str) {
+        self.clipboard.set_text(text);
+    }
+
+    fn clear(&self) {
+        self.clipboard.clear();
+    }
+}
+
+pub struct GtkBackend {
+    window: gtk::Window,
+    glarea: gtk::GLArea,
+    // Touchpad pinches arrive as a zoom gesture. Gestures are not owned by
+    // their widget, so this one has to be kept alive here.
+    zoom_gesture: gtk::GestureZoom,
+    im_context: gtk::IMMulticontext,
+    /// Whether a text field has focus and key presses should go through the
+    /// input method first.
+    ime_active: Cell<bool>,
+    clipboard: GtkClipboard,
+    /// The menu currently popped up, if any.
+    menu: Rc<RefCell<Option<gtk::Menu>>>,
+    // Native dialogs are not widgets and must be kept alive by whoever shows
+    // them.
+    file_chooser: RefCell<Option<gtk::FileChooserNative>>,
+    // Events that don't come from a signal handler (Servo waking the loop
+    // up, menu choices) are queued here and dispatched from the main loop.
+    sender: async_channel::Sender<BackendEvent>,
+    receiver: async_channel::Receiver<BackendEvent>,
+}
+
+impl GtkBackend {
+    pub fn new(title: &str) -> GtkBackend {
+        GtkBackend::with_options(title, &WindowOptions::default())
+    }
+
+    pub fn with_options(title: &str, options: &WindowOptions) -> GtkBackend {
+        gtk::init().expect("Initialization failed...");
+
+        let window = gtk::Window::new(gtk::WindowType::Toplevel);
+        window.set_title(title);
+        window.set_position(gtk::WindowPosition::Center);
+        window.set_default_size(1024, 768);
+        if options.transparent {
+            // Only screens with a compositing manager have an RGBA visual.
+            match WidgetExt::screen(&window).and_then(|screen| screen.rgba_visual()) {
+                Some(visual) => window.set_visual(Some(&visual)),
+                None => log::warn!("The screen doesn't support transparent windows"),
+            }
+            window.set_app_paintable(true);
+        }
+
+        let glarea = gtk::GLArea::new();
+        glarea.set_has_alpha(options.transparent);
+        glarea.add_events(
+            gdk::EventMask::POINTER_MOTION_MASK
+                | gdk::EventMask::BUTTON_PRESS_MASK
+                | gdk::EventMask::BUTTON_RELEASE_MASK
+                | gdk::EventMask::SCROLL_MASK
+                | gdk::EventMask::SMOOTH_SCROLL_MASK
+                | gdk::EventMask::TOUCH_MASK,
+        );
+        window.add(&glarea);
+
+        let zoom_gesture = gtk::GestureZoom::new(&glarea);
+        zoom_gesture.set_propagation_phase(gtk::PropagationPhase::Bubble);
+
+        // Showing the window realizes the GL area, which is what creates its
+        // GL context.
+        window.show_all();
+        glarea.make_current();
+
+        let im_context = gtk::IMMulticontext::new();
+        im_context.set_client_window(glarea.window().as_ref());
+
+        let (sender, receiver) = async_channel::unbounded();
+        GtkBackend {
+            window,
+            glarea,
+            zoom_gesture,
+            im_context,
+            ime_active: Cell::new(false),
+            clipboard: GtkClipboard {
+                clipboard: gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD),
+            },
+            menu: Rc::new(RefCell::new(None)),
+            file_chooser: RefCell::new(None),
+            sender,
+            receiver,
+        }
+    }
+
+    fn logical_to_device(&self) -> LogicalToDevice {
+        LogicalToDevice::new(self.glarea.scale_factor() as f32)
+    }
+
+    fn device_point(&self, (x, y): (f64, f64)) -> DevicePoint {
+        logical_point_to_device(x, y, self.logical_to_device())
+    }
+
+    /// Converts a GDK scroll event into a wheel delta in device pixels,
+    /// positive meaning up and left like glutin's.
+    fn wheel_delta(&self, event: &gdk::EventScroll) -> Option<WheelDelta> {
+        let scale = self.glarea.scale_factor() as f64;
+        let (x, y, mode) = match event.direction() {
+            gdk::ScrollDirection::Up => (0.0, LINE_HEIGHT, WheelMode::DeltaLine),
+            gdk::ScrollDirection::Down => (0.0, -LINE_HEIGHT, WheelMode::DeltaLine),
+            gdk::ScrollDirection::Left => (LINE_HEIGHT, 0.0, WheelMode::DeltaLine),
+            gdk::ScrollDirection::Right => (-LINE_HEIGHT, 0.0, WheelMode::DeltaLine),
+            gdk::ScrollDirection::Smooth => {
+                let (dx, dy) = event.delta();
+                (-dx * scale, -dy * scale, WheelMode::DeltaPixel)
+            }
+            _ => return None,
+        };
+        Some(WheelDelta { x, y, z: 0.0, mode })
+    }
+}
+
+/// Owns the backend and the embedder's event handler for as long as the GTK
+/// main loop runs; every signal handler holds a reference to it.
+struct Dispatcher<H> {
+    backend: GtkBackend,
+    handler: RefCell<H>,
+    polling: Cell<bool>,
+    /// The timeout standing in for `LoopControl::WaitUntil`, until it fires.
+    timeout: RefCell<Option<glib::SourceId>>,
+    /// Whether `BackendEvent::EventsCleared` is due once the main loop has
+    /// gone through the events waiting.
+    clear_pending: Cell<bool>,
+}
+
+impl<H> Dispatcher<H>
+where
+    H: FnMut(&dyn WindowBackend, BackendEvent) -> LoopControl + 'static,
+{
+    fn dispatch(self: &Rc<Self>, event: BackendEvent) {
+        if !matches!(event, BackendEvent::EventsCleared) && !self.clear_pending.replace(true) {
+            // GDK's events are handled at a higher priority than this, so
+            // all of the ones already waiting are delivered first.
+            let this = self.clone();
+            glib::idle_add_local_full(glib::Priority::HIGH_IDLE, move || {
+                this.clear_pending.set(false);
+                this.dispatch(BackendEvent::EventsCleared);
+                glib::ControlFlow::Break
+            });
+        }
+        let control = (self.handler.borrow_mut())(&self.backend, event);
+        // Like other event loops, forget about a deadline once the handler
+        // no longer asks for it.
+        if let Some(timeout) = self.timeout.take() {
+            timeout.remove();
+        }
+        match control {
+            LoopControl::Exit => gtk::main_quit(),
+            LoopControl::Poll if !self.polling.get() => {
+                // GTK has no notion of a polling main loop, so keep an idle
+                // callback queued for as long as the handler asks for one.
+                self.polling.set(true);
+                let this = self.clone();
+                glib::idle_add_local_once(move || {
+                    this.polling.set(false);
+                    this.dispatch(BackendEvent::Wake);
+                });
+            }
+            LoopControl::WaitUntil(deadline) => {
+                let this = self.clone();
+                let delay = deadline.saturating_duration_since(Instant::now());
+                let timeout = glib::timeout_add_local_once(delay, move || {
+                    this.timeout.take();
+                    this.dispatch(BackendEvent::Timeout);
+                });
+                *self.timeout.borrow_mut() = Some(timeout);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl WindowBackend for GtkBackend {
+    fn native_context(&self) -> Option<surfman::NativeContext> {
+        self.glarea.make_current();
+        let context = unsafe { cgl::CGLGetCurrentContext() };
+        Some(surfman::NativeContext(context))
+    }
+
+    fn connection(&self) -> surfman::Connection {
+        surfman::Connection::new().expect("Failed to create connection")
+    }
+
+    fn inner_size(&self) -> DeviceIntSize {
+        logical_size_to_device(
+            self.glarea.allocated_width(),
+            self.glarea.allocated_height(),
+            self.logical_to_device(),
+        )
+    }
+
+    fn screen_geometry(&self) -> ScreenGeometry {
+        let scale = self.logical_to_device();
+        let (x, y) = self.window.position();
+        let (width, height) = self.window.size();
+        let window_rect = DeviceIntRect::from_origin_and_size(
+            logical_point_to_device(x as f64, y as f64, scale).round().to_i32(),
+            logical_size_to_device(width, height, scale),
+        );
+        let monitor = self
+            .window
+            .window()
+            .and_then(|window| window.display().monitor_at_window(&window));
+        match monitor {
+            Some(monitor) => {
+                let geometry = monitor.geometry();
+                let workarea = monitor.workarea();
+                ScreenGeometry {
+                    screen_size: logical_size_to_device(geometry.width(), geometry.height(), scale),
+                    available_screen_size: logical_size_to_device(
+                        workarea.width(),
+                        workarea.height(),
+                        scale,
+                    ),
+                    window_rect,
+                    // GDK reports 0 when it doesn't know.
+                    refresh_rate: Some(monitor.refresh_rate() as f32 / 1000.0)
+                        .filter(|rate| *rate > 0.0),
+                }
+            }
+            None => ScreenGeometry {
+                screen_size: window_rect.size(),
+                available_screen_size: window_rect.size(),
+                window_rect,
+                refresh_rate: None,
+            },
+        }
+    }
+
+    fn scale_factor(&self) -> f32 {
+        self.glarea.scale_factor() as f32
+    }
+
+    fn make_current(&self) {
+        self.glarea.make_current();
+    }
+
+    fn present(&self) {
+        // The GL area swaps buffers itself once its render signal returns.
+    }
+
+    fn set_cursor(&self, cursor: Cursor) {
+        let name = match cursor {
+            Cursor::None => "default",
+            Cursor::Pointer => "pointer",
+            Cursor::Text => "text",
+            _ => return,
+        };
+        if let Some(window) = self.glarea.window() {
+            let cursor = gdk::Cursor::from_name(&window.display(), name);
+            window.set_cursor(cursor.as_ref());
+        }
+    }
+
+    fn set_title(&self, title: &str) {
+        self.window.set_title(title);
+    }
+
+    fn clipboard(&self) -> &dyn Clipboard {
+        &self.clipboard
+    }
+
+    fn show_ime(&self, input_type: InputMethodType, area: DeviceIntRect) {
+        self.im_context.set_input_purpose(input_purpose(input_type));
+        // The cursor location is in the GL area's logical pixels.
+        let scale = self.glarea.scale_factor();
+        self.im_context.set_cursor_location(&gdk::Rectangle::new(
+            area.min.x / scale,
+            area.min.y / scale,
+            area.width() / scale,
+            area.height() / scale,
+        ));
+        if !self.ime_active.replace(true) {
+            // The input method may answer with preedit or commit signals
+            // straight away, which mustn't reach the `App` while it is still
+            // busy with the event that showed it.
+            let im_context = self.im_context.clone();
+            glib::idle_add_local_once(move || im_context.focus_in());
+        }
+    }
+
+    fn hide_ime(&self) {
+        if self.ime_active.replace(false) {
+            // Resetting commits or drops what is being composed, through
+            // signals that are delivered right away, so see `show_ime`.
+            let im_context = self.im_context.clone();
+            glib::idle_add_local_once(move || {
+                im_context.reset();
+                im_context.focus_out();
+            });
+        }
+    }
+
+    fn show_menu(&self, anchor: DeviceIntRect, items: &[MenuItem]) -> bool {
+        let Some(window) = self.window.window() else {
+            return false;
+        };
+        let menu = gtk::Menu::new();
+        for (index, item) in items.iter().enumerate() {
+            let menu_item = if item.checked {
+                let check_item = gtk::CheckMenuItem::with_label(&item.label);
+                check_item.set_draw_as_radio(true);
+                check_item.set_active(true);
+                check_item.upcast()
+            } else {
+                gtk::MenuItem::with_label(&item.label)
+            };
+            menu_item.set_sensitive(item.enabled);
+            let current = self.menu.clone();
+            let sender = self.sender.clone();
+            let this_menu = menu.clone();
+            menu_item.connect_activate(move |_| {
+                if current.borrow().as_ref() == Some(&this_menu) {
+                    current.borrow_mut().take();
+                    let _ = sender.try_send(BackendEvent::MenuClosed(Some(index)));
+                }
+            });
+            menu.append(&menu_item);
+        }
+
+        // A menu is deactivated before the chosen item is activated, so
+        // wait until that has had a chance to happen before reporting a
+        // dismissal. Menus that were replaced by another one stay quiet.
+        let current = self.menu.clone();
+        let sender = self.sender.clone();
+        menu.connect_deactivate(move |menu| {
+            let current = current.clone();
+            let sender = sender.clone();
+            let menu = menu.clone();
+            glib::idle_add_local_once(move || {
+                if current.borrow().as_ref() == Some(&menu) {
+                    current.borrow_mut().take();
+                    let _ = sender.try_send(BackendEvent::MenuClosed(None));
+                }
+            });
+        });
+
+        // Menus are placed in logical pixels, relative to the toplevel.
+        let scale = self.glarea.scale_factor();
+        let (x, y) = self
+            .glarea
+            .translate_coordinates(&self.window, anchor.min.x / scale, anchor.min.y / scale)
+            .unwrap_or((anchor.min.x / scale, anchor.min.y / scale));
+        let rect = gdk::Rectangle::new(x, y, anchor.width() / scale, anchor.height() / scale);
+        // A dropdown is at least as wide as the element it belongs to.
+        menu.set_size_request(rect.width(), -1);
+        menu.show_all();
+        if let Some(previous) = self.menu.replace(Some(menu.clone())) {
+            previous.popdown();
+        }
+        menu.popup_at_rect(
+            &window,
+            &rect,
+            gdk::Gravity::SouthWest,
+            gdk::Gravity::NorthWest,
+            None,
+        );
+        true
+    }
+
+    fn pick_files(&self, extensions: &[String], multiple: bool) -> bool {
+        let chooser = gtk::FileChooserNative::new(
+            Some(if multiple { "Choose Files" } else { "Choose a File" }),
+            Some(&self.window),
+            gtk::FileChooserAction::Open,
+            Some("_Open"),
+            Some("_Cancel"),
+        );
+        chooser.set_select_multiple(multiple);
+        if !extensions.is_empty() {
+            // GTK's patterns are case sensitive.
+            let accepted = gtk::FileFilter::new();
+            accepted.set_name(Some("Accepted files"));
+            for extension in extensions {
+                accepted.add_pattern(&format!("*.{}", extension.to_lowercase()));
+                accepted.add_pattern(&format!("*.{}", extension.to_uppercase()));
+            }
+            chooser.add_filter(accepted);
+            // The page's list is only a hint, so anything may still be picked.
+            let all = gtk::FileFilter::new();
+            all.set_name(Some("All files"));
+            all.add_pattern("*");
+            chooser.add_filter(all);
+        }
+
+        let sender = self.sender.clone();
+        chooser.connect_response(move |chooser, response| {
+            let files = (response == gtk::ResponseType::Accept).then(|| chooser.filenames());
+            let _ = sender.try_send(BackendEvent::FilesPicked(files));
+        });
+        chooser.show();
+        *self.file_chooser.borrow_mut() = Some(chooser);
+        true
+    }
+
+    fn request_redraw(&self) {
+        self.glarea.queue_render();
+    }
+
+    fn create_event_loop_waker(&self) -> Box<dyn EventLoopWaker> {
+        Box::new(Waker(self.sender.clone()))
+    }
+
+    fn run<H>(self, handler: H)
+    where
+        H: FnMut(&dyn WindowBackend, BackendEvent) -> LoopControl + 'static,
+    {
+        let receiver = self.receiver.clone();
+        let dispatcher = Rc::new(Dispatcher {
+            backend: self,
+            handler: RefCell::new(handler),
+            polling: Cell::new(false),
+            timeout: RefCell::new(None),
+            clear_pending: Cell::new(false),
+        });
+        let window = dispatcher.backend.window.clone();
+        let glarea = dispatcher.backend.glarea.clone();
+
+        let d = dispatcher.clone();
+        window.connect_delete_event(move |_, _| {
+            d.dispatch(BackendEvent::CloseRequested);
+            glib::Propagation::Stop
+        });
+
+        let d = dispatcher.clone();
+        window.connect_configure_event(move |_, _| {
+            d.dispatch(BackendEvent::Moved);
+            false
+        });
+
+        let d = dispatcher.clone();
+        glarea.connect_render(move |_, _| {
+            d.dispatch(BackendEvent::RedrawRequested);
+            glib::Propagation::Stop
+        });
+
+        // The GL area reports its new size in device pixels.
+        let d = dispatcher.clone();
+        glarea.connect_resize(move |_, width, height| {
+            if width > 0 && height > 0 {
+                d.dispatch(BackendEvent::Resized(DeviceIntSize::new(width, height)));
+            }
+        });
+
+        // GTK 3 only has integer scale factors; the GL area's framebuffer is
+        // resized to match, but it does not emit `resize` for that.
+        let d = dispatcher.clone();
+        glarea.connect_scale_factor_notify(move |_| {
+            d.dispatch(BackendEvent::ScaleFactorChanged {
+                scale_factor: d.backend.scale_factor(),
+                size: d.backend.inner_size(),
+            });
+        });
+
+        let d = dispatcher.clone();
+        glarea.connect_motion_notify_event(move |_, event| {
+            let position = d.backend.device_point(event.position());
+            d.dispatch(BackendEvent::CursorMoved(position));
+            glib::Propagation::Proceed
+        });
+
+        let d = dispatcher.clone();
+        glarea.connect_button_press_event(move |_, event| {
+            if event.event_type() == gdk::EventType::ButtonPress {
+                if let Some(button) = gdk_button_to_servo_button(event.button()) {
+                    d.dispatch(BackendEvent::MouseInput { button, pressed: true });
+                }
+            }
+            glib::Propagation::Proceed
+        });
+
+        let d = dispatcher.clone();
+        glarea.connect_button_release_event(move |_, event| {
+            if let Some(button) = gdk_button_to_servo_button(event.button()) {
+                d.dispatch(BackendEvent::MouseInput { button, pressed: false });
+            }
+            glib::Propagation::Proceed
+        });
+
+        let d = dispatcher.clone();
+        glarea.connect_touch_event(move |_, event| {
+            let phase = match event.event_type() {
+                gdk::EventType::TouchBegin => TouchEventType::Down,
+                gdk::EventType::TouchUpdate => TouchEventType::Move,
+                gdk::EventType::TouchEnd => TouchEventType::Up,
+                gdk::EventType::TouchCancel => TouchEventType::Cancel,
+                _ => return glib::Propagation::Proceed,
+            };
+            let Some(touch) = event.downcast_ref::<gdk::EventTouch>() else {
+                return glib::Propagation::Proceed;
+            };
+            // A sequence is an opaque pointer that stays the same for as long
+            // as the finger is down.
+            let id = touch
+                .event_sequence()
+                .map_or(0, |sequence| sequence.as_ptr() as usize as u64);
+            d.dispatch(BackendEvent::Touch {
+                id,
+                phase,
+                position: d.backend.device_point(touch.position()),
+            });
+            glib::Propagation::Stop
+        });
+
+        let d = dispatcher.clone();
+        glarea.connect_scroll_event(move |_, event| {
+            if let Some(delta) = d.backend.wheel_delta(event) {
+                let phase = if event.is_stop() {
+                    TouchEventType::Up
+                } else {
+                    TouchEventType::Move
+                };
+                d.dispatch(BackendEvent::Wheel {
+                    delta,
+                    phase,
+                    modifiers: modifiers(event.state()),
+                });
+            }
+            glib::Propagation::Stop
+        });
+
+        // Servo wants the change since the previous update rather than since
+        // the gesture began.
+        let zoom_gesture = dispatcher.backend.zoom_gesture.clone();
+        let last_scale = Rc::new(Cell::new(1.0));
+        let last_scale2 = last_scale.clone();
+        zoom_gesture.connect_begin(move |_, _| last_scale2.set(1.0));
+        let d = dispatcher.clone();
+        zoom_gesture.connect_scale_changed(move |_, scale| {
+            let magnification = scale / last_scale.replace(scale);
+            d.dispatch(BackendEvent::PinchZoom(magnification as f32));
+        });
+        let d = dispatcher.clone();
+        let im_context = dispatcher.backend.im_context.clone();
+        im_context.connect_preedit_changed(move |im_context| {
+            let (text, _, _) = im_context.preedit_string();
+            d.dispatch(BackendEvent::Ime(ImeEvent::Preedit(text.to_string())));
+        });
+
+        let d = dispatcher.clone();
+        im_context.connect_commit(move |_, text| {
+            d.dispatch(BackendEvent::Ime(ImeEvent::Commit(text.to_owned())));
+        });
+
+        let d = dispatcher.clone();
+        window.connect_key_press_event(move |_, event| {
+            let backend = &d.backend;
+            if backend.ime_active.get() && backend.im_context.filter_keypress(event) {
+                return glib::Propagation::Stop;
+            }
+            if let Some(event) = keyboard_event(event, KeyState::Down) {
+                d.dispatch(BackendEvent::Keyboard(event));
+            }
+            glib::Propagation::Stop
+        });
+
+        let d = dispatcher.clone();
+        window.connect_key_release_event(move |_, event| {
+            let backend = &d.backend;
+            if backend.ime_active.get() && backend.im_context.filter_keypress(event) {
+                return glib::Propagation::Stop;
+            }
+            if let Some(event) = keyboard_event(event, KeyState::Up) {
+                d.dispatch(BackendEvent::Keyboard(event));
+            }
+            glib::Propagation::Stop
+        });
+
+        let d = dispatcher;
+        glib::spawn_future_local(async move {
+            while let Ok(event) = receiver.recv().await {
+                d.dispatch(event);
+            }
+        });
+
+        gtk::main();
+    }
+}
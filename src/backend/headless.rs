@@ -0,0 +1,286 @@
+use gleam::gl::{self, Gl};
+use servo::webrender_api::units::{DeviceIntPoint, DeviceIntRect, DeviceIntSize};
+use servo::{Cursor, EventLoopWaker, InputMethodType};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::time::Instant;
+use surfman::{
+    ContextAttributeFlags, ContextAttributes, GLApi, GLVersion, SurfaceAccess, SurfaceType,
+};
+
+use crate::clipboard::{Clipboard, MemoryClipboard};
+use crate::menu::MenuItem;
+
+use super::{BackendEvent, LoopControl, ScreenGeometry, WindowBackend};
+
+struct Waker(Sender<()>);
+impl EventLoopWaker for Waker {
+    fn clone_box(&self) -> Box<dyn EventLoopWaker> {
+        Box::new(Waker(self.0.clone()))
+    }
+    fn wake(&self) {
+        let _ = self.0.send(());
+    }
+}
+
+/// An offscreen GL context standing in for a window's, so that lepton draws
+/// frames exactly as it would to a window, for measuring that.
+struct OffscreenGl {
+    device: surfman::Device,
+    context: RefCell<surfman::Context>,
+    gl: Rc<dyn Gl>,
+    /// The framebuffer of the surface bound to the context, which stands in
+    /// for the window's default framebuffer.
+    framebuffer: Cell<u32>,
+}
+
+impl OffscreenGl {
+    fn new(connection: &surfman::Connection, size: DeviceIntSize) -> OffscreenGl {
+        let adapter = connection
+            .create_adapter()
+            .expect("Failed to create adapter");
+        let device = connection
+            .create_device(&adapter)
+            .expect("Failed to create device");
+        // Drivers may answer with a compatibility context, whose GLSL
+        // lepton's shaders adapt to.
+        let attributes = ContextAttributes {
+            version: GLVersion::new(3, 0),
+            flags: ContextAttributeFlags::ALPHA,
+        };
+        let descriptor = device
+            .create_context_descriptor(&attributes)
+            .expect("Failed to create context descriptor");
+        let mut context = device
+            .create_context(&descriptor, None)
+            .expect("Failed to create context");
+        bind_new_surface(&device, &mut context, size);
+        device
+            .make_context_current(&context)
+            .expect("Failed to make context current");
+
+        let gl = unsafe {
+            let get_proc_address = |symbol: &str| device.get_proc_address(&context, symbol);
+            match device.gl_api() {
+                GLApi::GL => gl::GlFns::load_with(get_proc_address),
+                GLApi::GLES => gl::GlesFns::load_with(get_proc_address),
+            }
+        };
+        let framebuffer = surface_framebuffer(&device, &context);
+
+        OffscreenGl {
+            device,
+            context: RefCell::new(context),
+            gl,
+            framebuffer: Cell::new(framebuffer),
+        }
+    }
+
+    /// Replaces the surface standing in for the window with one of `size`.
+    fn resize(&self, size: DeviceIntSize) {
+        let mut context = self.context.borrow_mut();
+        if let Ok(Some(mut surface)) = self.device.unbind_surface_from_context(&mut context) {
+            let _ = self.device.destroy_surface(&mut context, &mut surface);
+        }
+        bind_new_surface(&self.device, &mut context, size);
+        self.framebuffer.set(surface_framebuffer(&self.device, &context));
+    }
+}
+
+fn bind_new_surface(device: &surfman::Device, context: &mut surfman::Context, size: DeviceIntSize) {
+    let surface = device
+        .create_surface(
+            context,
+            SurfaceAccess::GPUOnly,
+            SurfaceType::Generic {
+                size: size.to_untyped(),
+            },
+        )
+        .expect("Failed to create surface");
+    device
+        .bind_surface_to_context(context, surface)
+        .map_err(|(error, _)| error)
+        .expect("Failed to bind surface");
+}
+
+fn surface_framebuffer(device: &surfman::Device, context: &surfman::Context) -> u32 {
+    device
+        .context_surface_info(context)
+        .ok()
+        .flatten()
+        .map_or(0, |info| info.framebuffer_object)
+}
+
+impl Drop for OffscreenGl {
+    fn drop(&mut self) {
+        let context = self.context.get_mut();
+        if let Ok(Some(mut surface)) = self.device.unbind_surface_from_context(context) {
+            let _ = self.device.destroy_surface(context, &mut surface);
+        }
+        let _ = self.device.destroy_context(context);
+    }
+}
+
+/// A backend with no window at all. Servo still renders into its offscreen
+/// surface, but nothing is ever shown.
+pub struct HeadlessBackend {
+    /// The size of the pretend window, which replayed resizes change.
+    size: Cell<DeviceIntSize>,
+    gl: Option<OffscreenGl>,
+    sender: Sender<()>,
+    receiver: Receiver<()>,
+    redraw_requested: Cell<bool>,
+    clipboard: MemoryClipboard,
+}
+
+impl HeadlessBackend {
+    pub fn new(size: DeviceIntSize) -> HeadlessBackend {
+        HeadlessBackend::create(size, None)
+    }
+
+    /// Draws frames into an offscreen GL context as if there was a window,
+    /// so the cost of that can be measured.
+    pub fn with_gl(size: DeviceIntSize) -> HeadlessBackend {
+        let connection = surfman::Connection::new().expect("Failed to create connection");
+        HeadlessBackend::create(size, Some(OffscreenGl::new(&connection, size)))
+    }
+
+    fn create(size: DeviceIntSize, gl: Option<OffscreenGl>) -> HeadlessBackend {
+        let (sender, receiver) = mpsc::channel();
+        HeadlessBackend {
+            size: Cell::new(size),
+            gl,
+            sender,
+            receiver,
+            redraw_requested: Cell::new(false),
+            clipboard: MemoryClipboard::default(),
+        }
+    }
+
+    fn next_event(&self, control: LoopControl) -> Option<BackendEvent> {
+        if self.redraw_requested.take() {
+            return Some(BackendEvent::RedrawRequested);
+        }
+        match control {
+            LoopControl::Poll => match self.receiver.try_recv() {
+                Ok(()) | Err(TryRecvError::Empty) => Some(BackendEvent::Wake),
+                Err(TryRecvError::Disconnected) => None,
+            },
+            LoopControl::WaitUntil(deadline) => {
+                match self.receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(()) => Some(BackendEvent::Wake),
+                    Err(RecvTimeoutError::Timeout) => Some(BackendEvent::Timeout),
+                    Err(RecvTimeoutError::Disconnected) => None,
+                }
+            }
+            _ => self.receiver.recv().ok().map(|()| BackendEvent::Wake),
+        }
+    }
+}
+
+impl WindowBackend for HeadlessBackend {
+    fn native_context(&self) -> Option<surfman::NativeContext> {
+        let gl = self.gl.as_ref()?;
+        Some(gl.device.native_context(&gl.context.borrow()))
+    }
+
+    fn connection(&self) -> surfman::Connection {
+        surfman::Connection::new().expect("Failed to create connection")
+    }
+
+    fn inner_size(&self) -> DeviceIntSize {
+        self.size.get()
+    }
+
+    fn set_inner_size(&self, size: DeviceIntSize) {
+        if size == self.size.get() {
+            return;
+        }
+        self.size.set(size);
+        if let Some(gl) = &self.gl {
+            gl.resize(size);
+        }
+    }
+
+    fn screen_geometry(&self) -> ScreenGeometry {
+        let size = self.size.get();
+        ScreenGeometry {
+            screen_size: size,
+            available_screen_size: size,
+            window_rect: DeviceIntRect::from_origin_and_size(DeviceIntPoint::zero(), size),
+            refresh_rate: None,
+        }
+    }
+
+    fn scale_factor(&self) -> f32 {
+        1.0
+    }
+
+    fn make_current(&self) {
+        if let Some(gl) = &self.gl {
+            gl.device
+                .make_context_current(&gl.context.borrow())
+                .expect("Failed to make context current");
+            gl.gl.bind_framebuffer(gl::FRAMEBUFFER, gl.framebuffer.get());
+        }
+    }
+
+    fn present(&self) {
+        // Nothing is shown, but waiting for the frame to be drawn accounts
+        // for the GPU's share of the work, as swapping buffers would.
+        if let Some(gl) = &self.gl {
+            gl.gl.finish();
+        }
+    }
+
+    fn set_cursor(&self, _cursor: Cursor) {}
+
+    fn set_title(&self, title: &str) {
+        log::info!("Title changed: {}", title);
+    }
+
+    fn clipboard(&self) -> &dyn Clipboard {
+        &self.clipboard
+    }
+
+    fn show_ime(&self, _input_type: InputMethodType, _area: DeviceIntRect) {}
+
+    fn hide_ime(&self) {}
+
+    fn show_menu(&self, _anchor: DeviceIntRect, _items: &[MenuItem]) -> bool {
+        false
+    }
+
+    fn pick_files(&self, _extensions: &[String], _multiple: bool) -> bool {
+        // There's nobody to ask; see `--pick-file`.
+        false
+    }
+
+    fn request_redraw(&self) {
+        self.redraw_requested.set(true);
+    }
+
+    fn create_event_loop_waker(&self) -> Box<dyn EventLoopWaker> {
+        Box::new(Waker(self.sender.clone()))
+    }
+
+    fn run<H>(self, mut handler: H)
+    where
+        H: FnMut(&dyn WindowBackend, BackendEvent) -> LoopControl + 'static,
+    {
+        let mut control = LoopControl::Wait;
+        while let Some(event) = self.next_event(control) {
+            control = handler(&self, event);
+            if control == LoopControl::Exit {
+                break;
+            }
+            // Events are delivered one at a time, so there's never another
+            // one waiting to be batched with this one.
+            control = handler(&self, BackendEvent::EventsCleared);
+            if control == LoopControl::Exit {
+                break;
+            }
+        }
+    }
+}
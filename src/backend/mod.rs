@@ -0,0 +1,187 @@
+//! The windowing toolkits lepton can host Servo in.
+//!
+//! Each front-end translates its native events into `BackendEvent`s and
+//! exposes the handful of window operations the embedder core needs through
+//! `WindowBackend`, so the code driving Servo only has to be written once.
+
+use crate::clipboard::Clipboard;
+use crate::menu::MenuItem;
+use servo::keyboard_types::{KeyboardEvent, Modifiers};
+use servo::script_traits::{TouchEventType, WheelDelta};
+use servo::webrender_api::units::{DeviceIntRect, DeviceIntSize, DevicePoint};
+use servo::{Cursor, EventLoopWaker, InputMethodType, MouseButton};
+use std::path::PathBuf;
+use std::time::Instant;
+
+// FIXME: the windowed backends only build on macOS, the one platform where
+// they can hand their toolkit's GL context to surfman. glutin may create a
+// GLX context elsewhere, which surfman can't wrap.
+#[cfg(all(feature = "glutin_window", target_os = "macos"))]
+mod glutin_keys;
+#[cfg(all(feature = "glutin_window", target_os = "macos"))]
+pub mod glutin_window;
+#[cfg(all(feature = "gtk_window", target_os = "macos"))]
+mod gtk_keys;
+#[cfg(all(feature = "gtk_window", target_os = "macos"))]
+pub mod gtk_window;
+pub mod headless;
+
+/// How many pixels one line of wheel scrolling moves.
+pub const LINE_HEIGHT: f64 = 38.0;
+
+/// A windowing event, already translated out of the toolkit's own types.
+/// Positions and sizes are in device pixels.
+#[derive(Debug)]
+pub enum BackendEvent {
+    Resized(DeviceIntSize),
+    /// The window's density changed, typically because it moved to another
+    /// monitor. `size` is the new drawable size in device pixels.
+    ScaleFactorChanged { scale_factor: f32, size: DeviceIntSize },
+    /// The window moved, possibly onto another monitor.
+    Moved,
+    CloseRequested,
+    CursorMoved(DevicePoint),
+    MouseInput { button: MouseButton, pressed: bool },
+    /// A wheel or touchpad scroll. Line deltas are already converted to
+    /// pixels using `LINE_HEIGHT`; positive values scroll up and left.
+    Wheel { delta: WheelDelta, phase: TouchEventType, modifiers: Modifiers },
+    /// A touchscreen contact starting, moving or ending. `id` tells apart
+    /// the fingers that are down at the same time.
+    Touch { id: u64, phase: TouchEventType, position: DevicePoint },
+    /// A touchpad pinch, as the ratio between the new and previous scale.
+    /// It magnifies the viewport, and leaves the page zoom level alone.
+    PinchZoom(f32),
+    Keyboard(KeyboardEvent),
+    Ime(ImeEvent),
+    /// A menu opened with `WindowBackend::show_menu` went away, with the
+    /// index of the chosen item if any.
+    MenuClosed(Option<usize>),
+    /// The user is done with the picker opened by `WindowBackend::pick_files`.
+    /// `None` means it was cancelled.
+    FilesPicked(Option<Vec<PathBuf>>),
+    RedrawRequested,
+    /// Servo woke up the event loop from another thread.
+    Wake,
+    /// Every event that was waiting has been delivered. Sent once per turn
+    /// of the event loop, after any other events.
+    EventsCleared,
+    /// The time the handler asked to be woken up at with
+    /// `LoopControl::WaitUntil` came.
+    Timeout,
+}
+
+/// Activity of the platform's input method, which composes text that can't
+/// be typed directly (CJK input, dead keys, handwriting...).
+#[derive(Debug)]
+pub enum ImeEvent {
+    /// The text being composed changed. It is empty when the composition was
+    /// abandoned, or just before it is committed.
+    Preedit(String),
+    /// Composition finished and produced this text.
+    Commit(String),
+    /// The input method was switched off.
+    Disabled,
+}
+
+/// Where the window is and what it is displayed on, in device pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScreenGeometry {
+    /// The size of the monitor the window is on.
+    pub screen_size: DeviceIntSize,
+    /// The part of that monitor not taken up by panels and docks.
+    pub available_screen_size: DeviceIntSize,
+    /// The window's outer frame, relative to the top left of the desktop.
+    pub window_rect: DeviceIntRect,
+    /// How many times a second the monitor refreshes, if known.
+    pub refresh_rate: Option<f32>,
+}
+
+/// How a front-end's window is created.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WindowOptions {
+    /// Ask for a window with an alpha channel, which the desktop shows
+    /// through. Needs a compositing window manager on X11.
+    pub transparent: bool,
+}
+
+/// What the event loop should do once an event has been handled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LoopControl {
+    /// Sleep until the next event arrives.
+    Wait,
+    /// Sleep until the next event arrives, or deliver a
+    /// `BackendEvent::Timeout` at the given time if none has by then.
+    WaitUntil(Instant),
+    /// Keep spinning; something on the page is animating.
+    Poll,
+    Exit,
+}
+
+pub trait WindowBackend {
+    /// The GL context the toolkit draws the window with, in a form surfman
+    /// can wrap. Returns `None` when there is nothing on screen to draw to.
+    fn native_context(&self) -> Option<surfman::NativeContext>;
+
+    /// A surfman connection to the display server the window lives on.
+    fn connection(&self) -> surfman::Connection;
+
+    /// The size of the drawable area.
+    fn inner_size(&self) -> DeviceIntSize;
+
+    /// Makes the drawable area `size`, for a replayed resize. Windows keep
+    /// the size the user gave them, so only backends without one follow.
+    fn set_inner_size(&self, _size: DeviceIntSize) {}
+
+    fn screen_geometry(&self) -> ScreenGeometry;
+
+    /// The number of device pixels per CSS pixel.
+    fn scale_factor(&self) -> f32;
+
+    /// Makes the window's GL context current on this thread.
+    fn make_current(&self);
+
+    /// Shows whatever was drawn since the last call.
+    fn present(&self);
+
+    fn set_cursor(&self, cursor: Cursor);
+
+    fn set_title(&self, title: &str);
+
+    /// The clipboard content copies to and pastes from.
+    fn clipboard(&self) -> &dyn Clipboard;
+
+    /// Enables the input method for a focused text field of the given type,
+    /// placing its candidate window next to `area` (in device pixels,
+    /// relative to the drawable area).
+    fn show_ime(&self, input_type: InputMethodType, area: DeviceIntRect);
+
+    fn hide_ime(&self);
+
+    /// Pops up a native menu just below `anchor` (in device pixels, relative
+    /// to the drawable area), and delivers a `BackendEvent::MenuClosed` once
+    /// it goes away. Only one menu is shown at a time; showing another one
+    /// replaces it without a `MenuClosed` for the first.
+    ///
+    /// Returns `false` if the toolkit has no menus to offer, in which case
+    /// lepton draws its own.
+    fn show_menu(&self, anchor: DeviceIntRect, items: &[MenuItem]) -> bool;
+
+    /// Opens a file picker for a file input, offering files with one of
+    /// `extensions` (without the dot) if any are given, and delivers a
+    /// `BackendEvent::FilesPicked` once the user is done with it.
+    ///
+    /// Returns `false` if there is no way to ask the user.
+    fn pick_files(&self, extensions: &[String], multiple: bool) -> bool;
+
+    /// Asks for a `BackendEvent::RedrawRequested` to be delivered soon.
+    fn request_redraw(&self);
+
+    fn create_event_loop_waker(&self) -> Box<dyn EventLoopWaker>;
+
+    /// Runs the toolkit's event loop, passing every translated event to
+    /// `handler`, until the handler returns `LoopControl::Exit`.
+    fn run<H>(self, handler: H)
+    where
+        H: FnMut(&dyn WindowBackend, BackendEvent) -> LoopControl + 'static,
+        Self: Sized;
+}
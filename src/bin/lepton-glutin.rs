@@ -0,0 +1,14 @@
+#[cfg(target_os = "macos")]
+fn main() {
+    use lepton::backend::glutin_window::GlutinBackend;
+
+    let config = lepton::init();
+    let backend = GlutinBackend::with_options("A fantastic window!", &config.window_options());
+    lepton::run(backend, config);
+}
+
+#[cfg(not(target_os = "macos"))]
+fn main() {
+    eprintln!("lepton-glutin only runs on macOS for now; try lepton-headless");
+    std::process::exit(1);
+}
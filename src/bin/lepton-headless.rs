@@ -0,0 +1,20 @@
+use lepton::backend::headless::HeadlessBackend;
+use lepton::recording;
+use servo::webrender_api::units::DeviceIntSize;
+
+fn main() {
+    let config = lepton::init();
+    // A replay runs at the size it was recorded at.
+    let size = config
+        .replay
+        .as_deref()
+        .and_then(|path| recording::read_header(path).ok())
+        .map_or(DeviceIntSize::new(1024, 768), |header| header.size());
+    // Screenshots need something to draw into.
+    let backend = if config.screenshot_dir.is_some() {
+        HeadlessBackend::with_gl(size)
+    } else {
+        HeadlessBackend::new(size)
+    };
+    lepton::run(backend, config);
+}
@@ -0,0 +1,337 @@
+//! A message channel between pages and the embedding application.
+//!
+//! Pages from the configured origins get a `window.lepton` object once their
+//! head has been parsed, announced by a `lepton-ready` event on `window`:
+//!
+//! * `lepton.postMessage(data)` hands `data` to the handler set with
+//!   `App::set_message_handler`.
+//! * `lepton.call(method, params)` calls a method added with
+//!   `App::add_rpc_method`, and returns a promise of its result.
+//! * `lepton.addEventListener("message", listener)` listens for what the
+//!   application sends with `App::post_message` and `App::broadcast_message`,
+//!   as `MessageEvent`s.
+//!
+//! Everything sent either way has to be serializable as JSON. Pages reach
+//! lepton through `window.prompt`, which Servo hands to the embedder and
+//! blocks on until it answers, with a prefix no real prompt has; lepton
+//! reaches pages with `App::evaluate_javascript`.
+//!
+//! Servo doesn't say which frame a prompt came from, so each webview's
+//! bridge gets a random token, which only the top-level document it was
+//! defined in knows and which has to come with every prompt. Frames can't
+//! send anything without it, whatever their origin.
+
+use ipc_channel::ipc::IpcSender;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use servo::base::id::WebViewId;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::rc::Rc;
+
+use crate::app::App;
+
+/// What the bridge's prompts start with.
+const PROMPT_PREFIX: &str = "\u{0}lepton-bridge:";
+
+/// Defines `window.lepton`, given the allowed origins and the prompt prefix
+/// with the webview's token. Evaluates to whether it did.
+const BRIDGE_SCRIPT: &str = r#"(function (origins, prefix) {
+  if (window.lepton || window.top !== window || !origins.includes(window.location.origin)) {
+    return false;
+  }
+  // Taken now, so that nothing the page does later can see the prefix.
+  const prompt = window.prompt.bind(window);
+  const target = new EventTarget();
+  const send = function (envelope) {
+    const answer = prompt(prefix + JSON.stringify(envelope));
+    return answer === null ? null : JSON.parse(answer);
+  };
+  Object.defineProperty(window, "lepton", {
+    value: Object.freeze({
+      postMessage(data) {
+        send({ kind: "message", data: data });
+      },
+      call(method, params) {
+        return new Promise(function (resolve, reject) {
+          const answer = send({ kind: "call", method: method, params: params });
+          if (answer === null) {
+            reject(new Error("lepton didn't answer"));
+          } else if ("error" in answer) {
+            reject(new Error(answer.error));
+          } else {
+            resolve(answer.result);
+          }
+        });
+      },
+      addEventListener: target.addEventListener.bind(target),
+      removeEventListener: target.removeEventListener.bind(target),
+      _dispatch(data) {
+        target.dispatchEvent(new MessageEvent("message", { data: data }));
+      },
+    }),
+  });
+  window.dispatchEvent(new Event("lepton-ready"));
+  return true;
+})"#;
+
+/// A message a page sent with `lepton.postMessage`.
+#[derive(Clone, Debug)]
+pub struct BridgeMessage {
+    /// The webview showing the page, for replying with `App::post_message`.
+    pub webview: WebViewId,
+    /// The page's origin, e.g. `https://example.com`.
+    pub origin: String,
+    pub data: Value,
+}
+
+/// Called with every message pages send.
+pub type MessageHandler = Rc<dyn Fn(&mut App, BridgeMessage)>;
+
+/// A method pages can call, taking and returning JSON.
+pub(crate) type RpcMethod = Rc<dyn Fn(&mut App, Value) -> Result<Value, String>>;
+
+/// Wraps a method with typed parameters and result into one taking and
+/// returning JSON.
+pub(crate) fn rpc_method<P, R>(
+    method: impl Fn(&mut App, P) -> Result<R, String> + 'static,
+) -> RpcMethod
+where
+    P: DeserializeOwned,
+    R: Serialize,
+{
+    Rc::new(move |app, params| {
+        let params = serde_json::from_value(params)
+            .map_err(|error| format!("Invalid parameters: {}", error))?;
+        let result = method(app, params)?;
+        serde_json::to_value(result).map_err(|error| format!("Invalid result: {}", error))
+    })
+}
+
+/// What a page sends through its prompt.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub(crate) enum Envelope {
+    Message {
+        #[serde(default)]
+        data: Value,
+    },
+    Call {
+        method: String,
+        #[serde(default)]
+        params: Value,
+    },
+}
+
+/// The token and envelope in a prompt's message, if the prompt came from the
+/// bridge, or claims to.
+pub(crate) fn parse_prompt(message: &str) -> Option<(&str, Result<Envelope, String>)> {
+    let (token, json) = message.strip_prefix(PROMPT_PREFIX)?.split_once(':')?;
+    Some((token, serde_json::from_str(json).map_err(|error| error.to_string())))
+}
+
+/// Hands a page the answer to its bridge prompt. Returns `false` if the page
+/// stopped waiting for it, e.g. because its webview was closed meanwhile.
+pub(crate) fn reply(sender: &IpcSender<Option<String>>, answer: &Value) -> bool {
+    match sender.send(Some(answer.to_string())) {
+        Ok(()) => true,
+        Err(error) => {
+            log::debug!("A page went away before its bridge message was answered: {:?}", error);
+            false
+        }
+    }
+}
+
+/// A token no page can guess.
+fn new_token() -> String {
+    // `RandomState`s are keyed from the OS's randomness.
+    let half = || RandomState::new().build_hasher().finish();
+    format!("{:016x}{:016x}", half(), half())
+}
+
+/// The answer to a call, as the page's `lepton.call` expects it.
+pub(crate) fn call_answer(result: Result<Value, String>) -> Value {
+    match result {
+        Ok(result) => json!({ "result": result }),
+        Err(error) => json!({ "error": error }),
+    }
+}
+
+/// The bridge's state on the embedder's side.
+#[derive(Default)]
+pub(crate) struct Bridge {
+    /// The origins whose pages get `window.lepton`.
+    pub origins: Vec<String>,
+    pub handler: Option<MessageHandler>,
+    pub methods: HashMap<String, RpcMethod>,
+    /// The webviews `window.lepton` has been defined in, which broadcasts
+    /// go to.
+    pub webviews: Vec<WebViewId>,
+    /// The token each webview's bridge sends its prompts with.
+    tokens: HashMap<WebViewId, String>,
+}
+
+impl Bridge {
+    pub fn new(origins: Vec<String>) -> Bridge {
+        Bridge {
+            origins,
+            ..Bridge::default()
+        }
+    }
+
+    pub fn allows(&self, origin: &str) -> bool {
+        self.origins.iter().any(|allowed| allowed == origin)
+    }
+
+    /// The script that defines `window.lepton` in the top-level document
+    /// `webview` shows, if it is from one of the allowed origins and doesn't
+    /// have it yet.
+    pub fn injection_script(&mut self, webview: WebViewId) -> String {
+        let token = self.tokens.entry(webview).or_insert_with(new_token);
+        let prefix = format!("{}{}:", PROMPT_PREFIX, token);
+        format!("{}({}, {})", BRIDGE_SCRIPT, json!(self.origins), json!(prefix))
+    }
+
+    /// Whether a prompt with `token` came from the bridge in `webview`.
+    pub fn is_token_of(&self, webview: WebViewId, token: &str) -> bool {
+        self.tokens.get(&webview).is_some_and(|expected| expected == token)
+    }
+
+    /// Stops taking prompts from `webview` and sending it messages, until
+    /// the bridge is defined in it again.
+    pub fn forget(&mut self, webview: WebViewId) {
+        self.webviews.retain(|other| *other != webview);
+        self.tokens.remove(&webview);
+    }
+}
+
+/// The script that delivers `data` to the message listeners of a page from
+/// `origin`. Pages from elsewhere could have a `window.lepton` of their own.
+pub(crate) fn dispatch_script(origin: &str, data: &Value) -> String {
+    format!(
+        "window.location.origin === {} && window.lepton && window.lepton._dispatch({})",
+        json!(origin),
+        data
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ipc_channel::ipc;
+    use servo::base::id::{PipelineNamespace, PipelineNamespaceId};
+
+    /// Webviews to give bridges to. Only call it once per test, which has a
+    /// thread of its own to make ids on.
+    fn webviews() -> (WebViewId, WebViewId) {
+        PipelineNamespace::install(PipelineNamespaceId(1));
+        (WebViewId::new(), WebViewId::new())
+    }
+
+    fn prompt(token: &str, json: &str) -> String {
+        format!("{}{}:{}", PROMPT_PREFIX, token, json)
+    }
+
+    #[test]
+    fn bridge_prompts_are_parsed() {
+        let message = prompt("abc", r#"{"kind":"message","data":[1,2]}"#);
+        match parse_prompt(&message) {
+            Some(("abc", Ok(Envelope::Message { data }))) => assert_eq!(data, json!([1, 2])),
+            other => panic!("{:?}", other),
+        }
+        let message = prompt("abc", r#"{"kind":"call","method":"add"}"#);
+        match parse_prompt(&message) {
+            Some(("abc", Ok(Envelope::Call { method, params }))) => {
+                assert_eq!(method, "add");
+                assert_eq!(params, Value::Null);
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn other_prompts_are_not_the_bridges() {
+        assert!(parse_prompt("What is your name?").is_none());
+        assert!(parse_prompt("lepton-bridge:abc:{}").is_none());
+        // The token has to be followed by a colon.
+        assert!(parse_prompt(&format!("{}abc", PROMPT_PREFIX)).is_none());
+    }
+
+    #[test]
+    fn malformed_envelopes_keep_their_token() {
+        for json in [
+            "",
+            "not json",
+            r#"{"kind":"teleport"}"#,
+            r#"{"kind":"call"}"#,
+        ]
+        .iter()
+        {
+            match parse_prompt(&prompt("abc", json)) {
+                Some(("abc", Err(_))) => {}
+                other => panic!("{:?} gave {:?}", json, other),
+            }
+        }
+    }
+
+    #[test]
+    fn tokens_only_match_their_webview() {
+        let (first, second) = webviews();
+        let mut bridge = Bridge::new(vec!["https://example.com".to_owned()]);
+        let script = bridge.injection_script(first);
+        let token = bridge.tokens[&first].clone();
+        assert!(script.contains(&token));
+        assert!(bridge.is_token_of(first, &token));
+        assert!(!bridge.is_token_of(first, "0123456789abcdef0123456789abcdef"));
+        assert!(!bridge.is_token_of(first, ""));
+        assert!(!bridge.is_token_of(second, &token));
+
+        bridge.injection_script(second);
+        assert_ne!(bridge.tokens[&second], token);
+        // Defining the bridge again in the same document keeps its token.
+        bridge.injection_script(first);
+        assert!(bridge.is_token_of(first, &token));
+    }
+
+    #[test]
+    fn forgotten_webviews_get_a_new_token() {
+        let (webview, _) = webviews();
+        let mut bridge = Bridge::new(vec![]);
+        bridge.injection_script(webview);
+        let token = bridge.tokens[&webview].clone();
+        bridge.webviews.push(webview);
+        bridge.forget(webview);
+        assert!(!bridge.is_token_of(webview, &token));
+        assert!(bridge.webviews.is_empty());
+        bridge.injection_script(webview);
+        assert!(!bridge.is_token_of(webview, &token));
+    }
+
+    #[test]
+    fn replies_reach_a_waiting_page() {
+        let (sender, receiver) = ipc::channel().unwrap();
+        assert!(reply(&sender, &call_answer(Ok(json!(3)))));
+        assert_eq!(receiver.recv().unwrap(), Some(r#"{"result":3}"#.to_owned()));
+    }
+
+    #[test]
+    fn replies_to_a_page_that_went_away_are_dropped() {
+        let (sender, receiver) = ipc::channel::<Option<String>>().unwrap();
+        drop(receiver);
+        assert!(!reply(&sender, &call_answer(Err("too late".to_owned()))));
+    }
+
+    #[test]
+    fn call_answers_are_what_lepton_call_expects() {
+        assert_eq!(
+            call_answer(Ok(json!({"a": 1}))),
+            json!({"result": {"a": 1}})
+        );
+        assert_eq!(
+            call_answer(Err("nope".to_owned())),
+            json!({"error": "nope"})
+        );
+    }
+}
@@ -0,0 +1,78 @@
+//! Clipboards web content can copy to and paste from.
+
+use std::cell::RefCell;
+
+pub trait Clipboard {
+    /// Calls `callback` with the clipboard's text, if it holds any. The
+    /// desktop may take a while to answer, so the callback can run later,
+    /// from the event loop, rather than before this returns.
+    fn request_text(&self, callback: Box<dyn FnOnce(Option<String>)>);
+    fn set_text(&self, text: &str);
+    fn clear(&self);
+}
+
+/// A clipboard that lives only as long as the process, for headless runs
+/// where there is no desktop to share one with.
+#[derive(Default)]
+pub struct MemoryClipboard {
+    contents: RefCell<Option<String>>,
+}
+
+impl Clipboard for MemoryClipboard {
+    fn request_text(&self, callback: Box<dyn FnOnce(Option<String>)>) {
+        let text = self.contents.borrow().clone();
+        callback(text);
+    }
+
+    fn set_text(&self, text: &str) {
+        *self.contents.borrow_mut() = Some(text.to_owned());
+    }
+
+    fn clear(&self) {
+        *self.contents.borrow_mut() = None;
+    }
+}
+
+/// The desktop's clipboard, reached without going through a toolkit.
+#[cfg(all(feature = "glutin_window", target_os = "macos"))]
+pub struct SystemClipboard {
+    clipboard: RefCell<Option<arboard::Clipboard>>,
+}
+
+#[cfg(all(feature = "glutin_window", target_os = "macos"))]
+impl SystemClipboard {
+    pub fn new() -> SystemClipboard {
+        let clipboard = arboard::Clipboard::new()
+            .map_err(|error| log::warn!("Clipboard unavailable: {}", error))
+            .ok();
+        SystemClipboard {
+            clipboard: RefCell::new(clipboard),
+        }
+    }
+}
+
+#[cfg(all(feature = "glutin_window", target_os = "macos"))]
+impl Clipboard for SystemClipboard {
+    fn request_text(&self, callback: Box<dyn FnOnce(Option<String>)>) {
+        let text = self
+            .clipboard
+            .borrow_mut()
+            .as_mut()
+            .and_then(|clipboard| clipboard.get_text().ok());
+        callback(text);
+    }
+
+    fn set_text(&self, text: &str) {
+        let mut clipboard = self.clipboard.borrow_mut();
+        let Some(clipboard) = clipboard.as_mut() else {
+            return;
+        };
+        let _ = clipboard.set_text(text);
+    }
+
+    fn clear(&self) {
+        if let Some(clipboard) = self.clipboard.borrow_mut().as_mut() {
+            let _ = clipboard.clear();
+        }
+    }
+}
@@ -0,0 +1,200 @@
+//! Command-line configuration shared by every front-end.
+
+use servo::servo_url::ServoUrl;
+use std::path::PathBuf;
+
+use crate::backend::WindowOptions;
+
+const DEFAULT_URL: &str = "http://neverssl.com";
+/// The port WebDriver clients expect by default.
+const DEFAULT_WEBDRIVER_PORT: u16 = 7000;
+
+pub const USAGE: &str = "\
+Usage: lepton [options] [url]
+
+Options:
+    --no-touch-mouse-emulation  Don't turn touches into mouse events
+    --no-coalesce-mouse-moves   Pass every mouse move and wheel scroll on to
+                                the page, rather than one for each burst
+    --transparent               Let the desktop show through the window
+                                wherever the page is transparent
+    --max-fps=<n>               Draw at most <n> frames a second (default: the
+                                display's refresh rate)
+    --perf-hud                  Start with the performance HUD shown (toggle it
+                                with Ctrl+Shift+H, Cmd+Shift+H on macOS)
+    --perf-csv=<path>           Write the time taken by every frame to <path>
+                                at exit
+    --record=<path>             Record the input passed to the page to <path>,
+                                marking checkpoints with Ctrl+Shift+S
+                                (Cmd+Shift+S on macOS)
+    --replay=<path>             Load the page recorded in <path> and replay its
+                                input, ignoring the user's
+    --screenshot-dir=<dir>      Save a screenshot to <dir> at every checkpoint
+    --bridge-origin=<origin>    Give pages from <origin>, e.g.
+                                https://example.com, `window.lepton` for
+                                messaging the application (repeat for more)
+    --devtools[=<port>]         Start Servo's devtools server on <port> on
+                                localhost (default: any free port)
+    --webdriver[=<port>]        Start a WebDriver server on <port> on every
+                                interface (default: 7000), if built with the
+                                `webdriver` feature
+    --gl-debug                  Log the GL driver's debug messages
+    --log=<filter>              Which messages to log, e.g. `debug` or
+                                `info,input=trace` (default: $LEPTON_LOG or
+                                $RUST_LOG)
+    --log-file=<path>           Also write log messages to <path>, as JSON lines
+    --pick-file=<path>          Answer file inputs with <path> instead of asking
+                                (repeat for more files)";
+
+pub struct Config {
+    pub url: ServoUrl,
+    /// Also deliver the first finger of a touch as mouse events, for pages
+    /// that only listen for the mouse.
+    pub touch_mouse_emulation: bool,
+    /// Only pass the latest of the mouse moves that arrive together on to
+    /// the page, and add up the wheel scrolls that do.
+    pub coalesce_mouse_moves: bool,
+    /// Give the window an alpha channel and the page a transparent
+    /// background.
+    pub transparent: bool,
+    /// Draw no more frames a second than this, even on faster displays.
+    pub max_fps: Option<f32>,
+    /// Show the performance HUD from the start.
+    pub perf_hud: bool,
+    /// Where to write frame timings as CSV at exit.
+    pub perf_csv: Option<PathBuf>,
+    /// Where to record the input passed to the page.
+    pub record: Option<PathBuf>,
+    /// A recording to replay, whose page is loaded instead of `url`.
+    pub replay: Option<PathBuf>,
+    /// Where to save screenshots taken at checkpoints.
+    pub screenshot_dir: Option<PathBuf>,
+    /// The origins whose pages can message the application through
+    /// `window.lepton`.
+    pub bridge_origins: Vec<String>,
+    /// The port to start Servo's devtools server on, where 0 lets the
+    /// system pick one.
+    pub devtools_port: Option<u16>,
+    /// The port to start Servo's WebDriver server on.
+    pub webdriver_port: Option<u16>,
+    /// Files to give file inputs without showing a picker, for automated
+    /// runs.
+    pub picked_files: Vec<PathBuf>,
+    /// Pass the GL driver's `KHR_debug` messages about lepton's own drawing
+    /// on to the log.
+    pub gl_debug: bool,
+    /// Overrides the log filter from the environment.
+    pub log_filter: Option<String>,
+    /// Where to also write log messages, as JSON lines.
+    pub log_file: Option<PathBuf>,
+}
+
+impl Config {
+    pub fn from_args(args: impl Iterator<Item = String>) -> Result<Config, String> {
+        let mut url = None;
+        let mut touch_mouse_emulation = true;
+        let mut coalesce_mouse_moves = true;
+        let mut transparent = false;
+        let mut max_fps = None;
+        let mut perf_hud = false;
+        let mut perf_csv = None;
+        let mut record = None;
+        let mut replay = None;
+        let mut screenshot_dir = None;
+        let mut bridge_origins = vec![];
+        let mut devtools_port = None;
+        let mut webdriver_port = None;
+        let mut picked_files = vec![];
+        let mut gl_debug = false;
+        let mut log_filter = None;
+        let mut log_file = None;
+        for arg in args {
+            match arg.as_str() {
+                "--no-touch-mouse-emulation" => touch_mouse_emulation = false,
+                "--no-coalesce-mouse-moves" => coalesce_mouse_moves = false,
+                "--transparent" => transparent = true,
+                "--perf-hud" => perf_hud = true,
+                "--gl-debug" => gl_debug = true,
+                "--devtools" => devtools_port = Some(0),
+                _ if arg.starts_with("--bridge-origin=") => {
+                    bridge_origins.push(arg["--bridge-origin=".len()..].to_owned());
+                }
+                _ if arg.starts_with("--devtools=") => {
+                    let value = &arg["--devtools=".len()..];
+                    match value.parse::<u16>() {
+                        Ok(port) => devtools_port = Some(port),
+                        Err(_) => return Err(format!("Invalid port {}", value)),
+                    }
+                }
+                "--webdriver" => webdriver_port = Some(DEFAULT_WEBDRIVER_PORT),
+                _ if arg.starts_with("--webdriver=") => {
+                    let value = &arg["--webdriver=".len()..];
+                    match value.parse::<u16>() {
+                        Ok(port) => webdriver_port = Some(port),
+                        Err(_) => return Err(format!("Invalid port {}", value)),
+                    }
+                }
+                _ if arg.starts_with("--pick-file=") => {
+                    picked_files.push(PathBuf::from(&arg["--pick-file=".len()..]));
+                }
+                _ if arg.starts_with("--max-fps=") => {
+                    let value = &arg["--max-fps=".len()..];
+                    match value.parse::<f32>() {
+                        Ok(fps) if fps > 0.0 => max_fps = Some(fps),
+                        _ => return Err(format!("Invalid frame rate {}", value)),
+                    }
+                }
+                _ if arg.starts_with("--perf-csv=") => {
+                    perf_csv = Some(PathBuf::from(&arg["--perf-csv=".len()..]));
+                }
+                _ if arg.starts_with("--record=") => {
+                    record = Some(PathBuf::from(&arg["--record=".len()..]));
+                }
+                _ if arg.starts_with("--replay=") => {
+                    replay = Some(PathBuf::from(&arg["--replay=".len()..]));
+                }
+                _ if arg.starts_with("--screenshot-dir=") => {
+                    screenshot_dir = Some(PathBuf::from(&arg["--screenshot-dir=".len()..]));
+                }
+                _ if arg.starts_with("--log=") => {
+                    log_filter = Some(arg["--log=".len()..].to_owned());
+                }
+                _ if arg.starts_with("--log-file=") => {
+                    log_file = Some(PathBuf::from(&arg["--log-file=".len()..]));
+                }
+                _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+                _ if url.is_none() => url = Some(arg),
+                _ => return Err(format!("Unexpected argument {}", arg)),
+            }
+        }
+        let url = url.as_deref().unwrap_or(DEFAULT_URL);
+        let url =
+            ServoUrl::parse(url).map_err(|error| format!("Invalid URL {}: {}", url, error))?;
+        Ok(Config {
+            url,
+            touch_mouse_emulation,
+            coalesce_mouse_moves,
+            transparent,
+            max_fps,
+            perf_hud,
+            perf_csv,
+            record,
+            replay,
+            screenshot_dir,
+            bridge_origins,
+            devtools_port,
+            webdriver_port,
+            picked_files,
+            gl_debug,
+            log_filter,
+            log_file,
+        })
+    }
+
+    /// What the front-end's window has to be created with.
+    pub fn window_options(&self) -> WindowOptions {
+        WindowOptions {
+            transparent: self.transparent,
+        }
+    }
+}
@@ -0,0 +1,37 @@
+//! The actions offered when content asks for a context menu.
+//!
+//! Pages can supply items of their own; lepton adds navigation, link and
+//! developer tools items after them, followed by any the embedding
+//! application registered with `App::add_context_menu_item`.
+
+use servo::base::id::WebViewId;
+use servo::servo_url::ServoUrl;
+use std::rc::Rc;
+
+use crate::app::App;
+
+/// What a context menu was opened on.
+#[derive(Clone, Debug)]
+pub struct ContextMenuTarget {
+    /// The webview the menu was opened in.
+    pub webview_id: WebViewId,
+    /// The page the menu was opened in.
+    pub page_url: Option<ServoUrl>,
+    /// The link under the cursor, if any.
+    pub link_url: Option<ServoUrl>,
+}
+
+/// A context menu item added by the embedding application.
+pub type ContextMenuCallback = Rc<dyn Fn(&mut App, &ContextMenuTarget)>;
+
+pub(crate) enum ContextMenuAction {
+    /// One of the items the page asked for, by index.
+    Page(usize),
+    Back,
+    Forward,
+    Reload,
+    CopyLink,
+    OpenLinkInNewWebView,
+    Inspect,
+    Custom(ContextMenuCallback),
+}
@@ -0,0 +1,138 @@
+//! Conversions between the logical pixels toolkits report input in and the
+//! device pixels Servo expects.
+
+use euclid::{Box2D, Scale, Size2D};
+use servo::webrender_api::units::{DeviceIntRect, DeviceIntSize, DevicePixel, DevicePoint};
+
+/// A toolkit's logical (density-independent) pixel.
+#[derive(Clone, Copy, Debug)]
+pub enum LogicalPixel {}
+
+pub type LogicalToDevice = Scale<f32, LogicalPixel, DevicePixel>;
+
+pub fn logical_point_to_device(x: f64, y: f64, scale: LogicalToDevice) -> DevicePoint {
+    euclid::Point2D::<f32, LogicalPixel>::new(x as f32, y as f32) * scale
+}
+
+/// Rounds to the nearest device pixel, the way toolkits size their
+/// framebuffers at fractional scale factors.
+pub fn logical_size_to_device(width: i32, height: i32, scale: LogicalToDevice) -> DeviceIntSize {
+    (Size2D::<f32, LogicalPixel>::new(width as f32, height as f32) * scale)
+        .round()
+        .to_i32()
+}
+
+/// Re-expresses a point that was in device pixels at `old` scale in device
+/// pixels at `new` scale, e.g. the last known cursor position after the
+/// window moved to a monitor with a different density.
+pub fn rescale_device_point(point: DevicePoint, old: f32, new: f32) -> DevicePoint {
+    point * Scale::<f32, DevicePixel, DevicePixel>::new(new / old)
+}
+
+/// Converts device pixels into the density-independent units Servo uses for
+/// window and screen geometry.
+pub fn device_size_to_independent<U>(size: DeviceIntSize, hidpi_factor: f32) -> Size2D<i32, U> {
+    (size.to_f32() / hidpi_factor).round().to_i32().cast_unit()
+}
+
+pub fn device_rect_to_independent<U>(rect: DeviceIntRect, hidpi_factor: f32) -> Box2D<i32, U> {
+    (rect.to_f32() / hidpi_factor).round().to_i32().cast_unit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use euclid::{Point2D, Size2D};
+
+    const SCALES: [f32; 3] = [1.0, 1.5, 2.0];
+
+    fn scale(factor: f32) -> LogicalToDevice {
+        LogicalToDevice::new(factor)
+    }
+
+    #[test]
+    fn logical_points_scale_to_device_pixels() {
+        let expected = [(10.0, 25.5), (15.0, 38.25), (20.0, 51.0)];
+        for (factor, (x, y)) in SCALES.iter().copied().zip(expected) {
+            let point = logical_point_to_device(10.0, 25.5, scale(factor));
+            assert_eq!(point, DevicePoint::new(x, y), "at {}x", factor);
+        }
+    }
+
+    #[test]
+    fn logical_sizes_scale_to_device_pixels() {
+        let expected = [(800, 600), (1200, 900), (1600, 1200)];
+        for (factor, (width, height)) in SCALES.iter().copied().zip(expected) {
+            let size = logical_size_to_device(800, 600, scale(factor));
+            assert_eq!(size, DeviceIntSize::new(width, height), "at {}x", factor);
+        }
+    }
+
+    #[test]
+    fn odd_logical_sizes_round_to_the_nearest_device_pixel() {
+        assert_eq!(
+            logical_size_to_device(801, 601, scale(1.5)),
+            DeviceIntSize::new(1202, 902)
+        );
+        assert_eq!(
+            logical_size_to_device(3, 1, scale(1.5)),
+            DeviceIntSize::new(5, 2)
+        );
+        assert_eq!(
+            logical_size_to_device(801, 601, scale(2.0)),
+            DeviceIntSize::new(1602, 1202)
+        );
+    }
+
+    #[test]
+    fn device_sizes_round_trip_through_independent_pixels() {
+        for factor in SCALES {
+            let device = logical_size_to_device(800, 600, scale(factor));
+            let independent: Size2D<i32, LogicalPixel> = device_size_to_independent(device, factor);
+            assert_eq!(independent, Size2D::new(800, 600), "at {}x", factor);
+        }
+    }
+
+    #[test]
+    fn odd_device_sizes_round_to_the_nearest_independent_pixel() {
+        let size: Size2D<i32, LogicalPixel> =
+            device_size_to_independent(DeviceIntSize::new(1203, 901), 1.5);
+        assert_eq!(size, Size2D::new(802, 601));
+        let size: Size2D<i32, LogicalPixel> =
+            device_size_to_independent(DeviceIntSize::new(1203, 901), 2.0);
+        assert_eq!(size, Size2D::new(602, 451));
+    }
+
+    #[test]
+    fn device_rects_convert_to_independent_pixels() {
+        let rect = DeviceIntRect::new(Point2D::new(30, 60), Point2D::new(1230, 960));
+        let expected = [
+            ((30, 60), (1230, 960)),
+            ((20, 40), (820, 640)),
+            ((15, 30), (615, 480)),
+        ];
+        for (factor, (min, max)) in SCALES.iter().copied().zip(expected) {
+            let independent: Box2D<i32, LogicalPixel> = device_rect_to_independent(rect, factor);
+            assert_eq!(
+                independent,
+                Box2D::new(min.into(), max.into()),
+                "at {}x",
+                factor
+            );
+        }
+    }
+
+    #[test]
+    fn device_points_rescale_between_densities() {
+        let point = DevicePoint::new(300.0, 150.0);
+        assert_eq!(
+            rescale_device_point(point, 1.5, 2.0),
+            DevicePoint::new(400.0, 200.0)
+        );
+        assert_eq!(
+            rescale_device_point(point, 1.5, 1.0),
+            DevicePoint::new(200.0, 100.0)
+        );
+        assert_eq!(rescale_device_point(point, 2.0, 2.0), point);
+    }
+}
@@ -0,0 +1,113 @@
+//! Choosing files for `<input type=file>`.
+
+use servo::FilterPattern;
+use std::path::{Path, PathBuf};
+
+/// The file extensions, without the dot, a file input accepts. Servo has
+/// already turned the MIME types in its `accept` attribute into extensions.
+pub fn accepted_extensions(filters: &[FilterPattern]) -> Vec<String> {
+    filters
+        .iter()
+        .map(|FilterPattern(extension)| extension.trim_start_matches('.').to_owned())
+        .filter(|extension| !extension.is_empty())
+        .collect()
+}
+
+/// Whether `path` has one of `extensions`, ignoring case. Any file will do
+/// when there are none.
+pub fn is_accepted(path: &Path, extensions: &[String]) -> bool {
+    if extensions.is_empty() {
+        return true;
+    }
+    let Some(extension) = path.extension().and_then(|extension| extension.to_str()) else {
+        return false;
+    };
+    extensions
+        .iter()
+        .any(|accepted| accepted.eq_ignore_ascii_case(extension))
+}
+
+/// Answers a file input from a list of files given up front rather than by
+/// asking the user, for automation. Returns `None`, as if the picker was
+/// cancelled, when none of them is accepted.
+pub fn scripted_selection(
+    files: &[PathBuf],
+    extensions: &[String],
+    multiple: bool,
+) -> Option<Vec<PathBuf>> {
+    let mut selection: Vec<PathBuf> = files
+        .iter()
+        .filter(|file| is_accepted(file, extensions))
+        .cloned()
+        .collect();
+    if !multiple {
+        selection.truncate(1);
+    }
+    (!selection.is_empty()).then_some(selection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(names: &[&str]) -> Vec<PathBuf> {
+        names.iter().map(PathBuf::from).collect()
+    }
+
+    fn extensions(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn extensions_lose_their_dot() {
+        let filters = [
+            FilterPattern(".png".to_owned()),
+            FilterPattern("jpg".to_owned()),
+            FilterPattern(".".to_owned()),
+        ];
+        assert_eq!(accepted_extensions(&filters), extensions(&["png", "jpg"]));
+    }
+
+    #[test]
+    fn extensions_match_ignoring_case() {
+        let accepted = extensions(&["png"]);
+        assert!(is_accepted(Path::new("/tmp/a.png"), &accepted));
+        assert!(is_accepted(Path::new("/tmp/a.PNG"), &accepted));
+        assert!(!is_accepted(Path::new("/tmp/a.jpg"), &accepted));
+        assert!(!is_accepted(Path::new("/tmp/png"), &accepted));
+        assert!(is_accepted(Path::new("/tmp/png"), &[]));
+    }
+
+    #[test]
+    fn scripted_selection_keeps_accepted_files_in_order() {
+        let files = paths(&["a.txt", "b.png", "c.jpg", "d.PNG"]);
+        let accepted = extensions(&["png", "jpg"]);
+        assert_eq!(
+            scripted_selection(&files, &accepted, true),
+            Some(paths(&["b.png", "c.jpg", "d.PNG"]))
+        );
+    }
+
+    #[test]
+    fn scripted_selection_takes_the_first_file_for_single_inputs() {
+        let files = paths(&["a.txt", "b.png", "c.png"]);
+        assert_eq!(
+            scripted_selection(&files, &extensions(&["png"]), false),
+            Some(paths(&["b.png"]))
+        );
+        assert_eq!(
+            scripted_selection(&files, &[], false),
+            Some(paths(&["a.txt"]))
+        );
+    }
+
+    #[test]
+    fn scripted_selection_cancels_when_nothing_is_accepted() {
+        let files = paths(&["a.txt"]);
+        assert_eq!(
+            scripted_selection(&files, &extensions(&["png"]), true),
+            None
+        );
+        assert_eq!(scripted_selection(&[], &[], true), None);
+    }
+}
@@ -0,0 +1,75 @@
+//! Handing the page's frames to embedders that draw the window themselves,
+//! for instance to show web content inside a 3D scene.
+//!
+//! An embedder that installs a `Painter` with `App::set_painter` is called
+//! whenever the window needs drawing, with the window's GL context current
+//! and the page's latest frame bound to a texture of that context.
+
+use servo::webrender_api::units::DeviceIntSize;
+use std::os::raw::c_void;
+use std::time::Duration;
+
+/// The page's latest frame, as a texture of the window's GL context.
+///
+/// The texture is only valid for the duration of the `Painter::paint` call
+/// it is passed to, since Servo reuses the surface behind it for later
+/// frames: it must not be kept, and anything that samples it must be drawn
+/// before returning. Servo flushes its rendering before a frame is handed
+/// over, so the texture is complete when `paint` is called.
+#[derive(Debug)]
+pub struct FrameTexture {
+    /// The GL texture name.
+    pub id: u32,
+    /// What the texture is bound as, usually `GL_TEXTURE_2D`. On macOS it is
+    /// `GL_TEXTURE_RECTANGLE`, addressed in texels rather than from 0 to 1.
+    pub target: u32,
+    /// Its size in texels. Rows come bottom first, as usual in GL.
+    pub size: DeviceIntSize,
+}
+
+/// What a `Painter` draws with.
+pub struct PaintContext<'a> {
+    pub(crate) device: &'a surfman::Device,
+    pub(crate) context: &'a surfman::Context,
+    /// The size of the window's framebuffer.
+    pub window_size: DeviceIntSize,
+}
+
+impl PaintContext<'_> {
+    /// Looks up a GL function of the window's context, for loading GL
+    /// bindings with.
+    pub fn get_proc_address(&self, name: &str) -> *const c_void {
+        self.device.get_proc_address(self.context, name)
+    }
+}
+
+/// Draws the window's contents in place of lepton, which otherwise fills the
+/// window with the page.
+pub trait Painter {
+    /// Draws a frame into the window's framebuffer, which is bound. lepton
+    /// draws its own menus on top afterwards, and presents the result.
+    fn paint(&mut self, frame: &FrameTexture, context: &PaintContext);
+}
+
+/// How long each part of drawing a frame took on the CPU. GL calls mostly
+/// queue work for the GPU, which may finish later.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameTimings {
+    /// Bringing Servo's latest rendering to its front buffer.
+    pub servo_present: Duration,
+    /// Making the window's context current and binding the front buffer to
+    /// a texture in it.
+    pub bind: Duration,
+    /// Drawing the page, or the painter's scene, and lepton's overlays.
+    pub draw: Duration,
+    /// Handing the front buffer back to Servo.
+    pub unbind: Duration,
+    /// Showing the result in the window.
+    pub present: Duration,
+}
+
+impl FrameTimings {
+    pub fn total(&self) -> Duration {
+        self.servo_present + self.bind + self.draw + self.unbind + self.present
+    }
+}
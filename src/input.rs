@@ -0,0 +1,286 @@
+//! Batching the events lepton sends Servo, so that a burst of input costs
+//! one round of Servo's event handling rather than one per event.
+//!
+//! Backends deliver events one at a time, then `BackendEvent::EventsCleared`
+//! once none are left. Events for Servo are queued until then, and pointer
+//! moves in a row are collapsed into the last one, since where the pointer
+//! ended up is all most pages care about. Wheel scrolls in a row are added
+//! up into one the same way.
+
+use servo::compositing::windowing::EmbedderEvent;
+use servo::script_traits::TouchEventType;
+use servo::webrender_api::ScrollLocation;
+
+/// Counts of the events lepton has passed on to Servo since startup.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InputStats {
+    /// Events queued for Servo, including pointer moves.
+    pub events: u64,
+    /// Pointer moves queued for Servo.
+    pub moves: u64,
+    /// Pointer moves dropped because a later one replaced them before Servo
+    /// saw them.
+    pub coalesced_moves: u64,
+    /// Wheel scrolls added to the one before them.
+    pub coalesced_scrolls: u64,
+    /// Batches handed to Servo, each one round of `handle_events`.
+    pub batches: u64,
+}
+
+pub(crate) struct EventQueue {
+    events: Vec<EmbedderEvent>,
+    /// Keep only the latest of consecutive pointer moves, and add up
+    /// consecutive wheel scrolls.
+    coalesce_moves: bool,
+    stats: InputStats,
+}
+
+impl EventQueue {
+    pub fn new(coalesce_moves: bool) -> EventQueue {
+        EventQueue {
+            events: vec![],
+            coalesce_moves,
+            stats: InputStats::default(),
+        }
+    }
+
+    pub fn push(&mut self, event: EmbedderEvent) {
+        self.stats.events += 1;
+        if let EmbedderEvent::MouseWindowMoveEventClass(_) = event {
+            self.stats.moves += 1;
+            if self.coalesce_moves {
+                if let Some(last @ EmbedderEvent::MouseWindowMoveEventClass(_)) = self.events.last_mut() {
+                    *last = event;
+                    self.stats.coalesced_moves += 1;
+                    return;
+                }
+            }
+        }
+        self.events.push(event);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Takes the events queued so far, to hand to Servo as one batch.
+    pub fn take(&mut self) -> Vec<EmbedderEvent> {
+        self.stats.batches += 1;
+        let events = std::mem::take(&mut self.events);
+        if !self.coalesce_moves {
+            return events;
+        }
+        self.add_up_scrolls(events)
+    }
+
+    /// Adds each wheel scroll to the one before it if nothing came in
+    /// between. A scroll is a `Wheel` event followed by a `Scroll` one, and
+    /// only those in the middle of a gesture are added up.
+    fn add_up_scrolls(&mut self, events: Vec<EmbedderEvent>) -> Vec<EmbedderEvent> {
+        let mut batch: Vec<EmbedderEvent> = Vec::with_capacity(events.len());
+        let mut events = events.into_iter().peekable();
+        while let Some(event) = events.next() {
+            if let (EmbedderEvent::Wheel(delta, position), Some(next)) = (&event, events.peek()) {
+                let last_two = batch.len().saturating_sub(2);
+                if let (
+                    EmbedderEvent::Scroll(
+                        ScrollLocation::Delta(scroll),
+                        point,
+                        TouchEventType::Move,
+                    ),
+                    [EmbedderEvent::Wheel(last_delta, last_position), EmbedderEvent::Scroll(
+                        ScrollLocation::Delta(last_scroll),
+                        last_point,
+                        TouchEventType::Move,
+                    )],
+                ) = (next, &mut batch[last_two..])
+                {
+                    if last_position == position
+                        && last_point == point
+                        && last_delta.mode == delta.mode
+                    {
+                        last_delta.x += delta.x;
+                        last_delta.y += delta.y;
+                        last_delta.z += delta.z;
+                        *last_scroll += *scroll;
+                        events.next();
+                        self.stats.coalesced_scrolls += 1;
+                        continue;
+                    }
+                }
+            }
+            batch.push(event);
+        }
+        batch
+    }
+
+    pub fn stats(&self) -> InputStats {
+        self.stats
+    }
+}
+
+impl Extend<EmbedderEvent> for EventQueue {
+    fn extend<I: IntoIterator<Item = EmbedderEvent>>(&mut self, events: I) {
+        for event in events {
+            self.push(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use servo::compositing::windowing::MouseWindowEvent;
+    use servo::keyboard_types::{Key, KeyboardEvent};
+    use servo::script_traits::{WheelDelta, WheelMode};
+    use servo::webrender_api::units::{DevicePoint, LayoutVector2D};
+    use servo::MouseButton;
+
+    fn mouse_move(x: f32) -> EmbedderEvent {
+        EmbedderEvent::MouseWindowMoveEventClass(DevicePoint::new(x, 0.0))
+    }
+
+    fn key(c: &str) -> EmbedderEvent {
+        EmbedderEvent::Keyboard(KeyboardEvent {
+            key: Key::Character(c.to_owned()),
+            ..KeyboardEvent::default()
+        })
+    }
+
+    fn mouse_down() -> EmbedderEvent {
+        EmbedderEvent::MouseWindowEventClass(MouseWindowEvent::MouseDown(
+            MouseButton::Left,
+            DevicePoint::new(0.0, 0.0),
+        ))
+    }
+
+    /// The two events `App::scroll` queues for a wheel scroll of `dy`.
+    fn scroll(dy: f64, phase: TouchEventType) -> [EmbedderEvent; 2] {
+        let position = DevicePoint::new(5.0, 5.0);
+        let delta = WheelDelta {
+            x: 0.0,
+            y: dy,
+            z: 0.0,
+            mode: WheelMode::DeltaPixel,
+        };
+        let location = ScrollLocation::Delta(LayoutVector2D::new(0.0, dy as f32));
+        [
+            EmbedderEvent::Wheel(delta, position),
+            EmbedderEvent::Scroll(location, position.to_i32(), phase),
+        ]
+    }
+
+    /// A short name for each event, to compare batches with.
+    fn describe(events: &[EmbedderEvent]) -> Vec<String> {
+        events
+            .iter()
+            .map(|event| match event {
+                EmbedderEvent::MouseWindowMoveEventClass(point) => format!("move {}", point.x),
+                EmbedderEvent::MouseWindowEventClass(MouseWindowEvent::MouseDown(..)) => {
+                    "down".to_owned()
+                }
+                EmbedderEvent::Keyboard(event) => format!("key {}", event.key),
+                EmbedderEvent::Wheel(delta, _) => format!("wheel {}", delta.y),
+                EmbedderEvent::Scroll(ScrollLocation::Delta(delta), _, _) => {
+                    format!("scroll {}", delta.y)
+                }
+                _ => "other".to_owned(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn consecutive_moves_keep_the_last() {
+        let mut queue = EventQueue::new(true);
+        queue.extend([mouse_move(1.0), mouse_move(2.0), mouse_move(3.0)]);
+        assert_eq!(describe(&queue.take()), ["move 3"]);
+        let stats = queue.stats();
+        assert_eq!(
+            (stats.events, stats.moves, stats.coalesced_moves),
+            (3, 3, 2)
+        );
+    }
+
+    #[test]
+    fn moves_around_keys_and_buttons_stay_in_order() {
+        let mut queue = EventQueue::new(true);
+        queue.extend([
+            mouse_move(1.0),
+            mouse_move(2.0),
+            mouse_down(),
+            mouse_move(3.0),
+            key("a"),
+            mouse_move(4.0),
+            mouse_move(5.0),
+            key("b"),
+        ]);
+        assert_eq!(
+            describe(&queue.take()),
+            ["move 2", "down", "move 3", "key a", "move 5", "key b"]
+        );
+    }
+
+    #[test]
+    fn consecutive_scrolls_add_up() {
+        let mut queue = EventQueue::new(true);
+        queue.extend(scroll(1.0, TouchEventType::Move));
+        queue.extend(scroll(2.0, TouchEventType::Move));
+        queue.extend(scroll(4.0, TouchEventType::Move));
+        assert_eq!(describe(&queue.take()), ["wheel 7", "scroll 7"]);
+        assert_eq!(queue.stats().coalesced_scrolls, 2);
+    }
+
+    #[test]
+    fn scrolls_are_not_added_across_other_input() {
+        let mut queue = EventQueue::new(true);
+        queue.extend(scroll(1.0, TouchEventType::Move));
+        queue.push(key("a"));
+        queue.extend(scroll(2.0, TouchEventType::Move));
+        queue.push(mouse_move(1.0));
+        queue.extend(scroll(4.0, TouchEventType::Move));
+        assert_eq!(
+            describe(&queue.take()),
+            [
+                "wheel 1", "scroll 1", "key a", "wheel 2", "scroll 2", "move 1", "wheel 4",
+                "scroll 4"
+            ]
+        );
+        assert_eq!(queue.stats().coalesced_scrolls, 0);
+    }
+
+    #[test]
+    fn the_ends_of_a_scroll_gesture_are_kept() {
+        let mut queue = EventQueue::new(true);
+        queue.extend(scroll(1.0, TouchEventType::Down));
+        queue.extend(scroll(2.0, TouchEventType::Move));
+        queue.extend(scroll(4.0, TouchEventType::Up));
+        assert_eq!(
+            describe(&queue.take()),
+            ["wheel 1", "scroll 1", "wheel 2", "scroll 2", "wheel 4", "scroll 4"]
+        );
+    }
+
+    #[test]
+    fn nothing_is_coalesced_when_turned_off() {
+        let mut queue = EventQueue::new(false);
+        queue.extend([mouse_move(1.0), mouse_move(2.0)]);
+        queue.extend(scroll(1.0, TouchEventType::Move));
+        queue.extend(scroll(2.0, TouchEventType::Move));
+        assert_eq!(
+            describe(&queue.take()),
+            ["move 1", "move 2", "wheel 1", "scroll 1", "wheel 2", "scroll 2"]
+        );
+        assert_eq!(queue.stats().coalesced_moves, 0);
+    }
+
+    #[test]
+    fn each_take_is_a_batch() {
+        let mut queue = EventQueue::new(true);
+        queue.push(mouse_move(1.0));
+        queue.take();
+        queue.push(mouse_move(2.0));
+        assert_eq!(describe(&queue.take()), ["move 2"]);
+        assert!(queue.is_empty());
+        assert_eq!(queue.stats().batches, 2);
+    }
+}
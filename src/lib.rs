@@ -0,0 +1,53 @@
+pub mod app;
+pub mod backend;
+pub mod bridge;
+pub mod clipboard;
+pub mod config;
+pub mod context_menu;
+pub mod coordinates;
+pub mod file_picker;
+pub mod frame;
+pub mod input;
+pub mod logging;
+pub mod menu;
+mod overlay;
+mod perf;
+mod quad;
+pub mod recording;
+pub mod scheduler;
+pub mod script;
+mod support;
+mod touch;
+pub mod zoom;
+
+use crate::app::App;
+use crate::backend::WindowBackend;
+use crate::config::{Config, USAGE};
+
+/// Does the process-wide setup every front-end needs, and returns the
+/// configuration given on the command line.
+pub fn init() -> Config {
+    rustls::crypto::ring::default_provider()
+        .install_default()
+        .expect("Error initializing crypto provider");
+
+    let config = match Config::from_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, USAGE);
+            std::process::exit(1);
+        }
+    };
+    if let Err(error) = logging::init(config.log_filter.as_deref(), config.log_file.as_deref()) {
+        eprintln!("Failed to set up logging: {}", error);
+        std::process::exit(1);
+    }
+    config
+}
+
+/// Starts a new Servo instance as configured and drives it from `backend`'s
+/// event loop until the window is closed.
+pub fn run<B: WindowBackend>(backend: B, config: Config) {
+    let mut app = App::new(&backend, config);
+    backend.run(move |backend, event| app.handle_event(backend, event));
+}
@@ -0,0 +1,128 @@
+//! lepton's logger, which Servo's own logging goes through as well.
+//!
+//! Messages are written to stderr, and optionally as JSON lines to a file.
+//! Which ones are shown is decided by a filter in `env_logger`'s syntax
+//! (`info`, `warn,embedder-msg=debug`...) taken from `--log`, then
+//! `LEPTON_LOG`, then `RUST_LOG`.
+//!
+//! `log` takes a single logger per process, and Servo's `setup_logging`
+//! insists on installing its own, so it isn't called once this one is in
+//! place. Servo's messages still end up here, they just aren't passed on to
+//! its constellation as well.
+//!
+//! lepton's messages are grouped into a few targets besides its module
+//! paths, so each kind can be turned up on its own.
+
+use env_filter::{Builder as FilterBuilder, Filter};
+use log::{Log, Metadata, Record};
+use std::fs::File;
+use std::io::{self, LineWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// Input events, as handed over by the backend.
+pub const INPUT: &str = "input";
+/// Messages from Servo to the embedder.
+pub const EMBEDDER_MSG: &str = "embedder-msg";
+/// lepton's own GL drawing.
+pub const GL: &str = "gl";
+/// Page loads and history traversal.
+pub const NAVIGATION: &str = "navigation";
+
+const DEFAULT_FILTER: &str = "warn,lepton=info,navigation=info,gl=info";
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+struct Logger {
+    filter: Filter,
+    start: Instant,
+    json: Option<Mutex<LineWriter<File>>>,
+}
+
+fn json_line(record: &Record) -> String {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |time| time.as_secs_f64());
+    let line = serde_json::json!({
+        "time": time,
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "thread": std::thread::current().name(),
+        "message": record.args().to_string(),
+    });
+    line.to_string()
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.filter.matches(record) {
+            return;
+        }
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let thread = std::thread::current();
+        let _ = writeln!(
+            io::stderr().lock(),
+            "[{:9.3} {:5} {} {}] {}",
+            elapsed,
+            record.level(),
+            record.target(),
+            thread.name().unwrap_or("<unnamed>"),
+            record.args()
+        );
+        if let Some(json) = &self.json {
+            let _ = writeln!(json.lock().unwrap(), "{}", json_line(record));
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(json) = &self.json {
+            let _ = json.lock().unwrap().flush();
+        }
+    }
+}
+
+/// Installs lepton's logger. `filter` overrides the one from the
+/// environment; `json_file` also gets every message as a JSON object per
+/// line. Has to be called before anything is logged, since messages logged
+/// earlier are lost.
+pub fn init(filter: Option<&str>, json_file: Option<&Path>) -> Result<(), String> {
+    let filter = filter
+        .map(str::to_owned)
+        .or_else(|| std::env::var("LEPTON_LOG").ok())
+        .or_else(|| std::env::var("RUST_LOG").ok())
+        .unwrap_or_else(|| DEFAULT_FILTER.to_owned());
+    let filter = FilterBuilder::new()
+        .try_parse(&filter)
+        .map_err(|error| format!("Invalid log filter: {}", error))?
+        .build();
+    let json = match json_file {
+        Some(path) => {
+            let file = File::create(path)
+                .map_err(|error| format!("Failed to create {}: {}", path.display(), error))?;
+            Some(Mutex::new(LineWriter::new(file)))
+        }
+        None => None,
+    };
+    let max_level = filter.filter();
+    let logger = Logger {
+        filter,
+        start: Instant::now(),
+        json,
+    };
+    log::set_boxed_logger(Box::new(logger)).map_err(|error| error.to_string())?;
+    log::set_max_level(max_level);
+    INSTALLED.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Whether `init` has installed lepton's logger. When it hasn't, `App::new`
+/// sets up Servo's own logger instead.
+pub fn is_installed() -> bool {
+    INSTALLED.load(Ordering::Relaxed)
+}
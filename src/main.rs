@@ -1,5 +0,0 @@
-#[cfg(feature = "gtk_window")]
-include!("main_gtk.rs");
-
-#[cfg(feature = "glutin_window")]
-include!("main_glutin.rs");
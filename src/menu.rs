@@ -0,0 +1,347 @@
+//! Popup menus, and lepton's own rendering of them for front-ends that
+//! can't show native ones.
+
+use embedded_graphics::mono_font::MonoFont;
+use embedded_graphics::pixelcolor::Rgb888;
+use euclid::default::Size2D;
+use servo::keyboard_types::Key;
+use servo::webrender_api::units::{DeviceIntPoint, DeviceIntRect, DeviceIntSize, DevicePoint};
+
+use crate::overlay::{font_for_scale, Canvas};
+
+const BACKGROUND: Rgb888 = Rgb888::new(0xf4, 0xf4, 0xf4);
+const BORDER: Rgb888 = Rgb888::new(0x99, 0x99, 0x99);
+const TEXT: Rgb888 = Rgb888::new(0x11, 0x11, 0x11);
+const DISABLED_TEXT: Rgb888 = Rgb888::new(0x99, 0x99, 0x99);
+const HIGHLIGHT: Rgb888 = Rgb888::new(0x33, 0x66, 0xcc);
+const HIGHLIGHTED_TEXT: Rgb888 = Rgb888::new(0xff, 0xff, 0xff);
+
+#[derive(Clone, Debug)]
+pub struct MenuItem {
+    pub label: String,
+    /// Disabled items are shown greyed out and can't be chosen.
+    pub enabled: bool,
+    /// Whether the item is marked as the current choice.
+    pub checked: bool,
+}
+
+impl MenuItem {
+    pub fn new(label: impl Into<String>) -> MenuItem {
+        MenuItem {
+            label: label.into(),
+            enabled: true,
+            checked: false,
+        }
+    }
+}
+
+/// What became of a menu after some input.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum MenuResponse {
+    Open,
+    /// The menu went away, with the index of the chosen item if any.
+    Closed(Option<usize>),
+}
+
+/// A menu drawn by lepton on top of the page.
+pub(crate) struct GlMenu {
+    items: Vec<MenuItem>,
+    origin: DeviceIntPoint,
+    size: DeviceIntSize,
+    item_height: i32,
+    padding: i32,
+    font: &'static MonoFont<'static>,
+    highlighted: Option<usize>,
+    /// Whether a button went down over the menu. The release of the click
+    /// that opened the menu must not choose whatever ends up under it.
+    pressed: bool,
+}
+
+impl GlMenu {
+    /// Lays out a menu opening below `anchor`, moved as needed to fit in a
+    /// window of `viewport` size.
+    pub fn new(
+        items: Vec<MenuItem>,
+        anchor: DeviceIntRect,
+        viewport: DeviceIntSize,
+        hidpi_factor: f32,
+    ) -> GlMenu {
+        let font = font_for_scale(hidpi_factor);
+        let padding = (4.0 * hidpi_factor).round() as i32;
+        let char_width = (font.character_size.width + font.character_spacing) as i32;
+        let item_height = font.character_size.height as i32 + 2 * padding;
+        let longest = items
+            .iter()
+            .map(|item| item.label.chars().count())
+            .max()
+            .unwrap_or(0) as i32;
+        // Leave room for the check mark on the left.
+        let width = (longest * char_width + 4 * padding + item_height).max(anchor.width());
+        let size = DeviceIntSize::new(width, item_height * items.len() as i32 + 2);
+
+        let mut origin = DeviceIntPoint::new(anchor.min.x, anchor.max.y);
+        if origin.x + size.width > viewport.width {
+            origin.x = viewport.width - size.width;
+        }
+        if origin.y + size.height > viewport.height && anchor.min.y - size.height >= 0 {
+            origin.y = anchor.min.y - size.height;
+        }
+        if origin.y + size.height > viewport.height {
+            origin.y = viewport.height - size.height;
+        }
+        let origin = origin.max(DeviceIntPoint::zero());
+
+        let highlighted = items.iter().position(|item| item.checked && item.enabled);
+        GlMenu {
+            items,
+            origin,
+            size,
+            item_height,
+            padding,
+            font,
+            highlighted,
+            pressed: false,
+        }
+    }
+
+    pub fn rect(&self) -> DeviceIntRect {
+        DeviceIntRect::from_origin_and_size(self.origin, self.size)
+    }
+
+    fn item_at(&self, point: DevicePoint) -> Option<usize> {
+        let point = point.to_i32();
+        if !self.rect().contains(point) {
+            return None;
+        }
+        let index = ((point.y - self.origin.y - 1) / self.item_height) as usize;
+        self.items
+            .get(index)
+            .filter(|item| item.enabled)
+            .map(|_| index)
+    }
+
+    pub fn mouse_moved(&mut self, point: DevicePoint) {
+        self.highlighted = self.item_at(point);
+    }
+
+    pub fn mouse_input(&mut self, point: DevicePoint, pressed: bool) -> MenuResponse {
+        let inside = self.rect().contains(point.to_i32());
+        if pressed {
+            if !inside {
+                return MenuResponse::Closed(None);
+            }
+            self.pressed = true;
+            return MenuResponse::Open;
+        }
+        match self.item_at(point) {
+            Some(index) if self.pressed => MenuResponse::Closed(Some(index)),
+            _ => MenuResponse::Open,
+        }
+    }
+
+    /// Moves the highlight by `step` items, skipping disabled ones and
+    /// stopping at either end.
+    fn move_highlight(&mut self, step: isize) {
+        let mut index = match self.highlighted {
+            Some(index) => index as isize,
+            None if step > 0 => -1,
+            None => self.items.len() as isize,
+        };
+        loop {
+            index += step;
+            if index < 0 {
+                return;
+            }
+            match self.items.get(index as usize) {
+                None => return,
+                Some(item) if item.enabled => break,
+                Some(_) => {}
+            }
+        }
+        self.highlighted = Some(index as usize);
+    }
+
+    pub fn key_down(&mut self, key: &Key) -> MenuResponse {
+        match key {
+            Key::ArrowDown => self.move_highlight(1),
+            Key::ArrowUp => self.move_highlight(-1),
+            Key::Home => {
+                self.highlighted = None;
+                self.move_highlight(1);
+            }
+            Key::End => {
+                self.highlighted = None;
+                self.move_highlight(-1);
+            }
+            Key::Enter => return MenuResponse::Closed(self.highlighted),
+            Key::Character(c) if c == " " => return MenuResponse::Closed(self.highlighted),
+            Key::Escape | Key::Tab => return MenuResponse::Closed(None),
+            _ => {}
+        }
+        MenuResponse::Open
+    }
+
+    pub fn render(&self) -> Canvas {
+        let size = Size2D::new(self.size.width, self.size.height);
+        let mut canvas = Canvas::new(size, BORDER);
+        canvas.fill_rect(1, 1, size.width - 2, size.height - 2, BACKGROUND);
+        let mark_size = self.font.character_size.height as i32 / 2;
+        for (index, item) in self.items.iter().enumerate() {
+            let top = 1 + index as i32 * self.item_height;
+            let color = if self.highlighted == Some(index) {
+                canvas.fill_rect(1, top, size.width - 2, self.item_height, HIGHLIGHT);
+                HIGHLIGHTED_TEXT
+            } else if item.enabled {
+                TEXT
+            } else {
+                DISABLED_TEXT
+            };
+            if item.checked {
+                let offset = (self.item_height - mark_size) / 2;
+                canvas.fill_rect(offset, top + offset, mark_size, mark_size, color);
+            }
+            let left = self.item_height + self.padding;
+            canvas.draw_text(&item.label, left, top + self.padding, self.font, color);
+        }
+        canvas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(enabled: &[bool]) -> Vec<MenuItem> {
+        enabled
+            .iter()
+            .enumerate()
+            .map(|(index, &enabled)| MenuItem {
+                enabled,
+                ..MenuItem::new(format!("Item {}", index))
+            })
+            .collect()
+    }
+
+    fn anchor(x: i32, y: i32) -> DeviceIntRect {
+        DeviceIntRect::from_origin_and_size(DeviceIntPoint::new(x, y), DeviceIntSize::new(0, 10))
+    }
+
+    fn menu(enabled: &[bool]) -> GlMenu {
+        GlMenu::new(
+            items(enabled),
+            anchor(0, 0),
+            DeviceIntSize::new(800, 600),
+            1.0,
+        )
+    }
+
+    /// The middle of item `index`.
+    fn item_point(menu: &GlMenu, index: i32) -> DevicePoint {
+        let rect = menu.rect();
+        let y = rect.min.y + 1 + index * menu.item_height + menu.item_height / 2;
+        DevicePoint::new((rect.min.x + rect.width() / 2) as f32, y as f32)
+    }
+
+    #[test]
+    fn arrows_skip_disabled_items() {
+        let mut menu = menu(&[false, true, false, false, true, false]);
+        menu.key_down(&Key::ArrowDown);
+        assert_eq!(menu.highlighted, Some(1));
+        menu.key_down(&Key::ArrowDown);
+        assert_eq!(menu.highlighted, Some(4));
+        menu.key_down(&Key::ArrowUp);
+        assert_eq!(menu.highlighted, Some(1));
+    }
+
+    #[test]
+    fn arrows_stop_at_either_end() {
+        let mut menu = menu(&[false, true, true, false]);
+        menu.move_highlight(1);
+        menu.move_highlight(1);
+        menu.move_highlight(1);
+        assert_eq!(menu.highlighted, Some(2));
+        menu.move_highlight(-1);
+        menu.move_highlight(-1);
+        assert_eq!(menu.highlighted, Some(1));
+    }
+
+    #[test]
+    fn home_and_end_pick_the_outermost_enabled_items() {
+        let mut menu = menu(&[false, true, true, true, false]);
+        menu.key_down(&Key::End);
+        assert_eq!(menu.highlighted, Some(3));
+        menu.key_down(&Key::Home);
+        assert_eq!(menu.highlighted, Some(1));
+    }
+
+    #[test]
+    fn nothing_is_highlighted_when_every_item_is_disabled() {
+        let mut menu = menu(&[false, false]);
+        menu.move_highlight(1);
+        menu.move_highlight(-1);
+        assert_eq!(menu.highlighted, None);
+    }
+
+    #[test]
+    fn the_checked_item_starts_highlighted() {
+        let mut items = items(&[true, true, true]);
+        items[2].checked = true;
+        let menu = GlMenu::new(items, anchor(0, 0), DeviceIntSize::new(800, 600), 1.0);
+        assert_eq!(menu.highlighted, Some(2));
+    }
+
+    #[test]
+    fn points_hit_the_item_under_them() {
+        let menu = menu(&[true, false, true]);
+        assert_eq!(menu.item_at(item_point(&menu, 0)), Some(0));
+        assert_eq!(menu.item_at(item_point(&menu, 2)), Some(2));
+        // Disabled items can't be hit.
+        assert_eq!(menu.item_at(item_point(&menu, 1)), None);
+    }
+
+    #[test]
+    fn points_outside_the_menu_hit_nothing() {
+        let menu = menu(&[true, true]);
+        let rect = menu.rect();
+        let below = DevicePoint::new(rect.min.x as f32 + 1.0, rect.max.y as f32 + 1.0);
+        let right = DevicePoint::new(rect.max.x as f32 + 1.0, rect.min.y as f32 + 1.0);
+        assert_eq!(menu.item_at(below), None);
+        assert_eq!(menu.item_at(right), None);
+    }
+
+    #[test]
+    fn menus_open_below_their_anchor() {
+        let menu = GlMenu::new(
+            items(&[true]),
+            anchor(20, 30),
+            DeviceIntSize::new(800, 600),
+            1.0,
+        );
+        assert_eq!(menu.rect().min, DeviceIntPoint::new(20, 40));
+    }
+
+    #[test]
+    fn menus_move_left_to_fit_in_the_window() {
+        let viewport = DeviceIntSize::new(800, 600);
+        let menu = GlMenu::new(items(&[true]), anchor(790, 30), viewport, 1.0);
+        assert_eq!(menu.rect().max.x, viewport.width);
+        assert_eq!(menu.rect().min.y, 40);
+    }
+
+    #[test]
+    fn menus_open_above_their_anchor_near_the_bottom() {
+        let viewport = DeviceIntSize::new(800, 600);
+        let menu = GlMenu::new(items(&[true, true]), anchor(20, 590), viewport, 1.0);
+        assert_eq!(menu.rect().max.y, 590);
+    }
+
+    #[test]
+    fn menus_too_tall_for_either_side_stay_in_the_window() {
+        let viewport = DeviceIntSize::new(800, 100);
+        let menu = GlMenu::new(items(&[true; 3]), anchor(20, 40), viewport, 1.0);
+        assert_eq!(menu.rect().max.y, viewport.height);
+        // Menus taller than the window keep their top in it.
+        let menu = GlMenu::new(items(&[true; 10]), anchor(20, 40), viewport, 1.0);
+        assert_eq!(menu.rect().min, DeviceIntPoint::new(20, 0));
+    }
+}
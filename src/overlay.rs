@@ -0,0 +1,94 @@
+//! Drawing for the bits of UI lepton shows on top of the page itself, for
+//! front-ends with no native widgets to use instead.
+//!
+//! Overlays are rasterized on the CPU into a `Canvas`, which is then copied
+//! into the window's framebuffer after Servo's frame.
+
+use embedded_graphics::mono_font::ascii::{FONT_10X20, FONT_9X15};
+use embedded_graphics::mono_font::{MonoFont, MonoTextStyle};
+use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+use embedded_graphics::text::{Baseline, Text};
+use euclid::default::Size2D;
+use std::convert::Infallible;
+
+/// The font overlays are drawn with at the given density. Bitmap fonts
+/// can't be scaled smoothly, so high density screens just get a larger one.
+pub fn font_for_scale(hidpi_factor: f32) -> &'static MonoFont<'static> {
+    if hidpi_factor >= 1.5 {
+        &FONT_10X20
+    } else {
+        &FONT_9X15
+    }
+}
+
+/// An RGBA image, stored top row first.
+pub struct Canvas {
+    size: Size2D<i32>,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    pub fn new(size: Size2D<i32>, background: Rgb888) -> Canvas {
+        let pixel = [background.r(), background.g(), background.b(), 0xff];
+        let count = (size.width.max(0) * size.height.max(0)) as usize;
+        Canvas {
+            size,
+            pixels: pixel.repeat(count),
+        }
+    }
+
+    pub fn size(&self) -> Size2D<i32> {
+        self.size
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn fill_rect(&mut self, x: i32, y: i32, width: i32, height: i32, color: Rgb888) {
+        let rectangle = Rectangle::new(Point::new(x, y), Size::new(width as u32, height as u32));
+        let _ = rectangle
+            .into_styled(PrimitiveStyle::with_fill(color))
+            .draw(self);
+    }
+
+    /// Draws `text` with its top left corner at `x`, `y`.
+    pub fn draw_text(
+        &mut self,
+        text: &str,
+        x: i32,
+        y: i32,
+        font: &'static MonoFont<'static>,
+        color: Rgb888,
+    ) {
+        let style = MonoTextStyle::new(font, color);
+        let _ = Text::with_baseline(text, Point::new(x, y), style, Baseline::Top).draw(self);
+    }
+}
+
+impl OriginDimensions for Canvas {
+    fn size(&self) -> Size {
+        Size::new(self.size.width as u32, self.size.height as u32)
+    }
+}
+
+impl DrawTarget for Canvas {
+    type Color = Rgb888;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Infallible>
+    where
+        I: IntoIterator<Item = Pixel<Rgb888>>,
+    {
+        for Pixel(point, color) in pixels {
+            if point.x < 0 || point.y < 0 || point.x >= self.size.width || point.y >= self.size.height {
+                continue;
+            }
+            let offset = ((point.y * self.size.width + point.x) * 4) as usize;
+            self.pixels[offset..offset + 3].copy_from_slice(&[color.r(), color.g(), color.b()]);
+        }
+        Ok(())
+    }
+}
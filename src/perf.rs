@@ -0,0 +1,224 @@
+//! Recording how long frames take, for the performance HUD and for dumping
+//! to CSV at exit.
+
+use embedded_graphics::mono_font::MonoFont;
+use embedded_graphics::pixelcolor::Rgb888;
+use euclid::default::Size2D;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::frame::FrameTimings;
+use crate::overlay::Canvas;
+
+/// How many of the latest frames the HUD shows.
+const HISTORY: usize = 120;
+/// The frame interval that fills the histogram's height.
+const HISTOGRAM_SCALE: Duration = Duration::from_millis(50);
+const HISTOGRAM_HEIGHT: i32 = 60;
+const BAR_WIDTH: i32 = 2;
+
+const BACKGROUND: Rgb888 = Rgb888::new(0x20, 0x20, 0x20);
+const TEXT: Rgb888 = Rgb888::new(0xee, 0xee, 0xee);
+const GOOD: Rgb888 = Rgb888::new(0x4c, 0xaf, 0x50);
+const SLOW: Rgb888 = Rgb888::new(0xff, 0xc1, 0x07);
+const JANK: Rgb888 = Rgb888::new(0xf4, 0x43, 0x36);
+
+#[derive(Clone, Copy, Debug)]
+struct FrameRecord {
+    /// When the frame was presented, since recording started.
+    time: Duration,
+    /// Since the previous frame was presented.
+    interval: Option<Duration>,
+    timings: FrameTimings,
+    /// Spent in Servo's `handle_events` since the previous frame.
+    handle_events: Duration,
+    /// The most messages for the embedder one call to `get_events` returned
+    /// since the previous frame.
+    messages: usize,
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn bar_color(interval: Duration) -> Rgb888 {
+    match interval.as_millis() {
+        0..=17 => GOOD,
+        18..=34 => SLOW,
+        _ => JANK,
+    }
+}
+
+pub struct PerfRecorder {
+    start: Instant,
+    last_frame: Option<Instant>,
+    handle_events: Duration,
+    messages: usize,
+    recent: VecDeque<FrameRecord>,
+    /// Where to write every frame's record at exit, and the records so far.
+    csv: Option<(PathBuf, Vec<FrameRecord>)>,
+}
+
+impl PerfRecorder {
+    pub fn new(csv_path: Option<PathBuf>) -> PerfRecorder {
+        PerfRecorder {
+            start: Instant::now(),
+            last_frame: None,
+            handle_events: Duration::ZERO,
+            messages: 0,
+            recent: VecDeque::with_capacity(HISTORY),
+            csv: csv_path.map(|path| (path, vec![])),
+        }
+    }
+
+    /// Records a call to Servo's `handle_events`, after which `get_events`
+    /// returned `messages` messages for the embedder.
+    pub fn events_handled(&mut self, duration: Duration, messages: usize) {
+        self.handle_events += duration;
+        self.messages = self.messages.max(messages);
+    }
+
+    pub fn frame_presented(&mut self, timings: FrameTimings) {
+        let now = Instant::now();
+        let record = FrameRecord {
+            time: now.duration_since(self.start),
+            interval: self.last_frame.map(|last_frame| now.duration_since(last_frame)),
+            timings,
+            handle_events: std::mem::take(&mut self.handle_events),
+            messages: std::mem::take(&mut self.messages),
+        };
+        self.last_frame = Some(now);
+        if self.recent.len() == HISTORY {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(record);
+        if let Some((_, records)) = &mut self.csv {
+            records.push(record);
+        }
+    }
+
+    fn mean(&self, get: impl Fn(&FrameRecord) -> Duration) -> Duration {
+        let count = self.recent.len().max(1) as u32;
+        self.recent.iter().map(get).sum::<Duration>() / count
+    }
+
+    /// The lines of text the HUD shows.
+    fn summary(&self) -> Vec<String> {
+        let mut intervals: Vec<Duration> =
+            self.recent.iter().filter_map(|record| record.interval).collect();
+        intervals.sort();
+        let fps = match (self.recent.front(), self.recent.back()) {
+            (Some(first), Some(last)) if last.time > first.time => {
+                (self.recent.len() - 1) as f64 / (last.time - first.time).as_secs_f64()
+            }
+            _ => 0.0,
+        };
+        let (mean_interval, p95_interval) = match intervals.len() {
+            0 => (Duration::ZERO, Duration::ZERO),
+            count => (
+                intervals.iter().sum::<Duration>() / count as u32,
+                intervals[(count - 1) * 95 / 100],
+            ),
+        };
+        let messages = self.recent.iter().map(|record| record.messages).max();
+        vec![
+            format!("{:5.1} fps", fps),
+            format!("frame   {:6.2} ms  p95 {:6.2}", millis(mean_interval), millis(p95_interval)),
+            format!(
+                "events  {:6.2} ms  msgs {}",
+                millis(self.mean(|record| record.handle_events)),
+                messages.unwrap_or(0)
+            ),
+            format!(
+                "present {:6.2} ms  bind {:6.2}",
+                millis(self.mean(|record| record.timings.servo_present)),
+                millis(self.mean(|record| record.timings.bind))
+            ),
+            format!(
+                "draw    {:6.2} ms  swap {:6.2}",
+                millis(self.mean(|record| record.timings.draw)),
+                millis(self.mean(|record| record.timings.present))
+            ),
+        ]
+    }
+
+    /// Draws the HUD: a summary of the latest frames, and a histogram of the
+    /// time between them with one bar per frame.
+    pub fn render_hud(&self, font: &'static MonoFont<'static>) -> Canvas {
+        let lines = self.summary();
+        let padding = font.character_size.height as i32 / 3;
+        let line_height = font.character_size.height as i32;
+        let longest = lines.iter().map(|line| line.len()).max().unwrap_or(0) as i32;
+        let text_width = longest * font.character_size.width as i32;
+        let histogram_width = HISTORY as i32 * BAR_WIDTH;
+        let size = Size2D::new(
+            text_width.max(histogram_width) + 2 * padding,
+            lines.len() as i32 * line_height + HISTOGRAM_HEIGHT + 3 * padding,
+        );
+
+        let mut canvas = Canvas::new(size, BACKGROUND);
+        for (index, line) in lines.iter().enumerate() {
+            let top = padding + index as i32 * line_height;
+            canvas.draw_text(line, padding, top, font, TEXT);
+        }
+        let baseline = size.height - padding;
+        for (index, interval) in self.recent.iter().filter_map(|record| record.interval).enumerate() {
+            let height = (interval.as_secs_f32() / HISTOGRAM_SCALE.as_secs_f32()
+                * HISTOGRAM_HEIGHT as f32)
+                .clamp(1.0, HISTOGRAM_HEIGHT as f32) as i32;
+            let left = padding + index as i32 * BAR_WIDTH;
+            canvas.fill_rect(left, baseline - height, BAR_WIDTH, height, bar_color(interval));
+        }
+        canvas
+    }
+
+    /// Writes every frame recorded so far to the CSV file, if one was asked
+    /// for. Only the first call writes anything.
+    pub fn write_csv(&mut self) {
+        let Some((path, records)) = self.csv.take() else {
+            return;
+        };
+        match write_records(&path, &records) {
+            Ok(()) => log::info!("Wrote {} frames to {}", records.len(), path.display()),
+            Err(error) => log::warn!("Failed to write {}: {}", path.display(), error),
+        }
+    }
+}
+
+impl Drop for PerfRecorder {
+    fn drop(&mut self) {
+        self.write_csv();
+    }
+}
+
+fn write_records(path: &Path, records: &[FrameRecord]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    writeln!(
+        file,
+        "time_s,interval_ms,servo_present_ms,bind_ms,draw_ms,unbind_ms,present_ms,total_ms,\
+         handle_events_ms,embedder_messages"
+    )?;
+    for record in records {
+        let timings = &record.timings;
+        writeln!(
+            file,
+            "{:.6},{},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3},{:.3},{}",
+            record.time.as_secs_f64(),
+            record
+                .interval
+                .map_or(String::new(), |interval| format!("{:.3}", millis(interval))),
+            millis(timings.servo_present),
+            millis(timings.bind),
+            millis(timings.draw),
+            millis(timings.unbind),
+            millis(timings.present),
+            millis(timings.total()),
+            millis(record.handle_events),
+            record.messages
+        )?;
+    }
+    file.flush()
+}
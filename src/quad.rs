@@ -0,0 +1,381 @@
+//! Drawing textures as quads with a small shader program, which unlike
+//! `glBlitFramebuffer` can blend, place the texture anywhere and works on
+//! GLES 2.
+//!
+//! The shaders are written in GLSL ES 1.00 and adapted to the version of
+//! GLSL the context takes when it is desktop GL, as it is with CGL on macOS:
+//! 1.50 for core profiles, older ones for the compatibility contexts some
+//! drivers give when asked for GL 3.0.
+
+use euclid::default::{Box2D, Size2D};
+use std::ffi::CString;
+use std::os::raw::c_void;
+use std::ptr;
+
+use crate::support::gl::types::{GLenum, GLint, GLuint};
+use crate::support::gl::{self, Gl};
+use crate::support::label_object;
+
+/// Not part of GLES, but what IOSurface-backed surfaces are bound as.
+pub const TEXTURE_RECTANGLE: GLenum = 0x84F5;
+
+const VERTEX_SHADER: &str = "
+attribute vec2 aPosition;
+uniform vec4 uDestRect;
+uniform vec4 uTexRect;
+varying vec2 vTexCoord;
+
+void main() {
+    vTexCoord = uTexRect.xy + aPosition * uTexRect.zw;
+    gl_Position = vec4(uDestRect.xy + aPosition * uDestRect.zw, 0.0, 1.0);
+}
+";
+
+const FRAGMENT_SHADER: &str = "
+uniform SAMPLER uTexture;
+uniform float uOpacity;
+varying vec2 vTexCoord;
+
+void main() {
+    // Textures hold premultiplied alpha, so opacity scales every channel.
+    gl_FragColor = TEXTURE(uTexture, vTexCoord) * uOpacity;
+}
+";
+
+/// The newest desktop GLSL the shaders need, which every core profile takes.
+const DESKTOP_GLSL: u32 = 150;
+
+/// The version of desktop GLSL to write shaders in, going by a context's
+/// `GL_SHADING_LANGUAGE_VERSION`, e.g. `4.60 NVIDIA` or `1.30`, as a number
+/// like GLSL's `#version`. Versions from 1.30 on take `in` and `out`, and
+/// older ones are all treated as 1.20.
+fn desktop_glsl_version(shading_language_version: &str) -> u32 {
+    let number = shading_language_version.split_whitespace().next().unwrap_or("");
+    let (major, minor) = number.split_once('.').unwrap_or((number, "0"));
+    let minor: String = minor.chars().take_while(char::is_ascii_digit).take(2).collect();
+    let version = match (major.parse::<u32>(), format!("{:0<2}", minor).parse::<u32>()) {
+        (Ok(major), Ok(minor)) => major * 100 + minor,
+        // Whoever can't say is most likely a core profile.
+        _ => DESKTOP_GLSL,
+    };
+    if version >= 130 {
+        version.min(DESKTOP_GLSL)
+    } else {
+        120
+    }
+}
+
+/// What goes before the vertex and fragment shaders to compile them as GLSL
+/// `version` (`None` for GLSL ES 1.00), sampling textures bound as `target`.
+fn shader_prefixes(version: Option<u32>, target: GLenum) -> (String, String) {
+    let version = match version {
+        Some(version) => version,
+        None => {
+            let sampler = "#define SAMPLER sampler2D\n#define TEXTURE texture2D\n";
+            return (
+                "#version 100\n".to_owned(),
+                format!("#version 100\nprecision mediump float;\n{}", sampler),
+            );
+        }
+    };
+    let mut vertex = format!("#version {}\n", version);
+    let mut fragment = vertex.clone();
+    if target == TEXTURE_RECTANGLE && version < 140 {
+        fragment += "#extension GL_ARB_texture_rectangle : enable\n";
+    }
+    if version >= 130 {
+        vertex += "#define attribute in\n#define varying out\n";
+        fragment += "#define varying in\nout vec4 fragColor;\n#define gl_FragColor fragColor\n";
+    }
+    let (sampler, texture) = match (target == TEXTURE_RECTANGLE, version >= 130) {
+        (true, true) => ("sampler2DRect", "texture"),
+        (true, false) => ("sampler2DRect", "texture2DRect"),
+        (false, true) => ("sampler2D", "texture"),
+        (false, false) => ("sampler2D", "texture2D"),
+    };
+    fragment += &format!("#define SAMPLER {}\n#define TEXTURE {}\n", sampler, texture);
+    (vertex, fragment)
+}
+
+/// The corners of the unit square, as a triangle strip.
+const QUAD: [f32; 8] = [0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0];
+
+/// How to draw a texture with `QuadRenderer::draw`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quad {
+    /// Where to draw, in framebuffer pixels with the origin at the top left.
+    pub dest: Box2D<f32>,
+    pub opacity: f32,
+    /// Set for textures stored top row first; GL's come bottom row first.
+    pub flip_y: bool,
+}
+
+struct Program {
+    program: GLuint,
+    dest_rect: GLint,
+    tex_rect: GLint,
+    opacity: GLint,
+    texture: GLint,
+}
+
+pub struct QuadRenderer {
+    /// One program per texture target, since they need different samplers.
+    texture_2d: Program,
+    texture_rectangle: Option<Program>,
+    buffer: GLuint,
+    vertex_array: Option<GLuint>,
+}
+
+fn compile_shader(gl: &Gl, kind: GLenum, source: &str) -> Result<GLuint, String> {
+    let source = CString::new(source).unwrap();
+    unsafe {
+        let shader = gl.CreateShader(kind);
+        gl.ShaderSource(shader, 1, &source.as_ptr(), ptr::null());
+        gl.CompileShader(shader);
+        let mut status = 0;
+        gl.GetShaderiv(shader, gl::COMPILE_STATUS, &mut status);
+        if status == 0 {
+            let mut log = vec![0u8; 1024];
+            let mut length = 0;
+            gl.GetShaderInfoLog(shader, log.len() as i32, &mut length, log.as_mut_ptr() as *mut _);
+            gl.DeleteShader(shader);
+            log.truncate(length.max(0) as usize);
+            return Err(format!("Shader failed to compile: {}", String::from_utf8_lossy(&log)));
+        }
+        Ok(shader)
+    }
+}
+
+fn link_program(gl: &Gl, vertex_source: &str, fragment_source: &str) -> Result<Program, String> {
+    let vertex_shader = compile_shader(gl, gl::VERTEX_SHADER, vertex_source)?;
+    let fragment_shader = compile_shader(gl, gl::FRAGMENT_SHADER, fragment_source)
+        .map_err(|error| {
+            unsafe { gl.DeleteShader(vertex_shader) };
+            error
+        })?;
+    unsafe {
+        let program = gl.CreateProgram();
+        gl.AttachShader(program, vertex_shader);
+        gl.AttachShader(program, fragment_shader);
+        gl.BindAttribLocation(program, 0, b"aPosition\0".as_ptr() as *const _);
+        gl.LinkProgram(program);
+        gl.DeleteShader(vertex_shader);
+        gl.DeleteShader(fragment_shader);
+        let mut status = 0;
+        gl.GetProgramiv(program, gl::LINK_STATUS, &mut status);
+        if status == 0 {
+            let mut log = vec![0u8; 1024];
+            let mut length = 0;
+            gl.GetProgramInfoLog(program, log.len() as i32, &mut length, log.as_mut_ptr() as *mut _);
+            gl.DeleteProgram(program);
+            log.truncate(length.max(0) as usize);
+            return Err(format!("Shaders failed to link: {}", String::from_utf8_lossy(&log)));
+        }
+        let uniform = |name: &[u8]| gl.GetUniformLocation(program, name.as_ptr() as *const _);
+        Ok(Program {
+            program,
+            dest_rect: uniform(b"uDestRect\0"),
+            tex_rect: uniform(b"uTexRect\0"),
+            opacity: uniform(b"uOpacity\0"),
+            texture: uniform(b"uTexture\0"),
+        })
+    }
+}
+
+fn build_program(gl: &Gl, glsl_version: Option<u32>, target: GLenum) -> Result<Program, String> {
+    let (vertex_prefix, fragment_prefix) = shader_prefixes(glsl_version, target);
+    let vertex_source = format!("{}{}", vertex_prefix, VERTEX_SHADER);
+    let fragment_source = format!("{}{}", fragment_prefix, FRAGMENT_SHADER);
+    let program = link_program(gl, &vertex_source, &fragment_source)?;
+    let label: &'static [u8] = if target == TEXTURE_RECTANGLE {
+        b"lepton quad (rectangle textures)\0"
+    } else {
+        b"lepton quad\0"
+    };
+    label_object(gl, gl::PROGRAM, program.program, label);
+    Ok(program)
+}
+
+impl QuadRenderer {
+    /// Sets up drawing with the current context, which is GLES if `is_gles`
+    /// is set. `shading_language_version` is its
+    /// `GL_SHADING_LANGUAGE_VERSION`.
+    pub fn new(gl: &Gl, is_gles: bool, shading_language_version: &str) -> Result<QuadRenderer, String> {
+        let glsl_version = if is_gles {
+            None
+        } else {
+            Some(desktop_glsl_version(shading_language_version))
+        };
+        let texture_2d = build_program(gl, glsl_version, gl::TEXTURE_2D)?;
+        // Rectangle textures only exist in desktop GL.
+        let texture_rectangle = if is_gles {
+            None
+        } else {
+            Some(build_program(gl, glsl_version, TEXTURE_RECTANGLE)?)
+        };
+
+        let mut buffer = 0;
+        let mut vertex_array = None;
+        unsafe {
+            // Core profiles can't draw without a vertex array object, and
+            // GLES 2 doesn't have them.
+            if gl.GenVertexArrays.is_loaded() {
+                let mut name = 0;
+                gl.GenVertexArrays(1, &mut name);
+                gl.BindVertexArray(name);
+                label_object(gl, gl::VERTEX_ARRAY, name, b"lepton quad\0");
+                vertex_array = Some(name);
+            }
+            gl.GenBuffers(1, &mut buffer);
+            gl.BindBuffer(gl::ARRAY_BUFFER, buffer);
+            label_object(gl, gl::BUFFER, buffer, b"lepton quad vertices\0");
+            gl.BufferData(
+                gl::ARRAY_BUFFER,
+                std::mem::size_of_val(&QUAD) as isize,
+                QUAD.as_ptr() as *const c_void,
+                gl::STATIC_DRAW,
+            );
+            if vertex_array.is_some() {
+                gl.EnableVertexAttribArray(0);
+                gl.VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, 0, ptr::null());
+                gl.BindVertexArray(0);
+            }
+            gl.BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+
+        Ok(QuadRenderer {
+            texture_2d,
+            texture_rectangle,
+            buffer,
+            vertex_array,
+        })
+    }
+
+    /// Draws `texture`, bound as `target`, into the current framebuffer,
+    /// blending it over what is already there.
+    pub fn draw(
+        &self,
+        gl: &Gl,
+        texture: GLuint,
+        target: GLenum,
+        texture_size: Size2D<i32>,
+        framebuffer_size: Size2D<i32>,
+        quad: &Quad,
+    ) {
+        let program = match (target, &self.texture_rectangle) {
+            (TEXTURE_RECTANGLE, Some(program)) => program,
+            _ => &self.texture_2d,
+        };
+
+        // Convert the destination to normalized device coordinates, whose
+        // origin is at the bottom left.
+        let width = framebuffer_size.width as f32;
+        let height = framebuffer_size.height as f32;
+        let dest = [
+            quad.dest.min.x / width * 2.0 - 1.0,
+            (height - quad.dest.max.y) / height * 2.0 - 1.0,
+            quad.dest.width() / width * 2.0,
+            quad.dest.height() / height * 2.0,
+        ];
+        // Rectangle textures are addressed in texels rather than 0 to 1.
+        let (tex_width, tex_height) = if target == TEXTURE_RECTANGLE {
+            (texture_size.width as f32, texture_size.height as f32)
+        } else {
+            (1.0, 1.0)
+        };
+        let tex = if quad.flip_y {
+            [0.0, tex_height, tex_width, -tex_height]
+        } else {
+            [0.0, 0.0, tex_width, tex_height]
+        };
+
+        unsafe {
+            gl.Viewport(0, 0, framebuffer_size.width, framebuffer_size.height);
+            gl.Disable(gl::DEPTH_TEST);
+            gl.Disable(gl::CULL_FACE);
+            gl.Enable(gl::BLEND);
+            gl.BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+
+            gl.UseProgram(program.program);
+            gl.Uniform4f(program.dest_rect, dest[0], dest[1], dest[2], dest[3]);
+            gl.Uniform4f(program.tex_rect, tex[0], tex[1], tex[2], tex[3]);
+            gl.Uniform1f(program.opacity, quad.opacity);
+            gl.Uniform1i(program.texture, 0);
+
+            gl.ActiveTexture(gl::TEXTURE0);
+            gl.BindTexture(target, texture);
+            gl.TexParameteri(target, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl.TexParameteri(target, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+
+            match self.vertex_array {
+                Some(vertex_array) => gl.BindVertexArray(vertex_array),
+                None => {
+                    gl.BindBuffer(gl::ARRAY_BUFFER, self.buffer);
+                    gl.EnableVertexAttribArray(0);
+                    gl.VertexAttribPointer(0, 2, gl::FLOAT, gl::FALSE, 0, ptr::null());
+                }
+            }
+            gl.DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
+
+            match self.vertex_array {
+                Some(_) => gl.BindVertexArray(0),
+                None => {
+                    gl.DisableVertexAttribArray(0);
+                    gl.BindBuffer(gl::ARRAY_BUFFER, 0);
+                }
+            }
+            gl.BindTexture(target, 0);
+            gl.UseProgram(0);
+            gl.Disable(gl::BLEND);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn core_profiles_get_glsl_150() {
+        assert_eq!(desktop_glsl_version("1.50"), 150);
+        assert_eq!(desktop_glsl_version("4.10"), 150);
+        assert_eq!(desktop_glsl_version("4.60 NVIDIA"), 150);
+        assert_eq!(desktop_glsl_version(""), 150);
+    }
+
+    #[test]
+    fn older_contexts_get_their_own_glsl() {
+        assert_eq!(desktop_glsl_version("1.30"), 130);
+        assert_eq!(desktop_glsl_version("1.40 Mesa"), 140);
+        assert_eq!(desktop_glsl_version("1.20"), 120);
+        assert_eq!(desktop_glsl_version("1.10"), 120);
+        assert_eq!(desktop_glsl_version("1.3"), 130);
+    }
+
+    #[test]
+    fn prefixes_start_with_the_version() {
+        let (vertex, fragment) = shader_prefixes(Some(130), gl::TEXTURE_2D);
+        assert!(vertex.starts_with("#version 130\n#define attribute in\n"));
+        assert!(fragment.starts_with("#version 130\n"));
+        assert!(fragment.contains("#define TEXTURE texture\n"));
+        let (vertex, fragment) = shader_prefixes(None, gl::TEXTURE_2D);
+        assert_eq!(vertex, "#version 100\n");
+        assert!(fragment.contains("precision mediump float;"));
+        assert!(fragment.contains("#define TEXTURE texture2D\n"));
+    }
+
+    #[test]
+    fn legacy_glsl_keeps_attributes_and_varyings() {
+        let (vertex, fragment) = shader_prefixes(Some(120), TEXTURE_RECTANGLE);
+        assert_eq!(vertex, "#version 120\n");
+        assert!(!fragment.contains("fragColor"));
+        // Rectangle textures need an extension before GLSL 1.40.
+        assert!(
+            fragment.starts_with("#version 120\n#extension GL_ARB_texture_rectangle : enable\n")
+        );
+        assert!(fragment.contains("#define TEXTURE texture2DRect\n"));
+        let (_, fragment) = shader_prefixes(Some(150), TEXTURE_RECTANGLE);
+        assert!(!fragment.contains("#extension"));
+        assert!(fragment.contains("#define SAMPLER sampler2DRect\n#define TEXTURE texture\n"));
+    }
+}
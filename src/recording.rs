@@ -0,0 +1,681 @@
+//! Recording the input handed to Servo, and replaying it into a fresh
+//! instance to reproduce a bug.
+//!
+//! Recordings are JSON lines: a header with the page and window geometry,
+//! then one entry per event. Times are in seconds since the page first
+//! finished loading, on both sides, so that a slow load doesn't shift the
+//! input against the page; input from before that is replayed right after
+//! the load. Checkpoints mark where a replay should take screenshots.
+//!
+//! Only what lepton itself sends Servo is recorded: mouse, wheel, touch,
+//! keyboard and input method events, zoom, history traversal, resizes and
+//! quitting. Answers to Servo's own requests, like menus and file pickers,
+//! aren't, and are given again by the replaying instance.
+
+use serde::{Deserialize, Serialize};
+use servo::base::id::WebViewId;
+use servo::compositing::windowing::{EmbedderEvent, MouseWindowEvent};
+use servo::keyboard_types::{
+    Code, CompositionEvent, CompositionState, Key, KeyState, KeyboardEvent, Location, Modifiers,
+};
+use servo::script_traits::{TouchEventType, TouchId, TraversalDirection, WheelDelta, WheelMode};
+use servo::webrender_api::units::{DeviceIntPoint, DeviceIntSize, DevicePoint, LayoutVector2D};
+use servo::webrender_api::ScrollLocation;
+use servo::MouseButton;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, LineWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// The first line of a recording.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Header {
+    pub url: String,
+    /// The size of the window's drawable area, in device pixels.
+    pub width: i32,
+    pub height: i32,
+    pub scale_factor: f32,
+}
+
+impl Header {
+    pub fn size(&self) -> DeviceIntSize {
+        DeviceIntSize::new(self.width, self.height)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ButtonAction {
+    Down,
+    Up,
+    Click,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Phase {
+    Down,
+    Move,
+    Up,
+    Cancel,
+}
+
+impl From<TouchEventType> for Phase {
+    fn from(phase: TouchEventType) -> Phase {
+        match phase {
+            TouchEventType::Down => Phase::Down,
+            TouchEventType::Move => Phase::Move,
+            TouchEventType::Up => Phase::Up,
+            TouchEventType::Cancel => Phase::Cancel,
+        }
+    }
+}
+
+impl From<Phase> for TouchEventType {
+    fn from(phase: Phase) -> TouchEventType {
+        match phase {
+            Phase::Down => TouchEventType::Down,
+            Phase::Move => TouchEventType::Move,
+            Phase::Up => TouchEventType::Up,
+            Phase::Cancel => TouchEventType::Cancel,
+        }
+    }
+}
+
+/// One recorded event. Positions are in the page, in device pixels.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum RecordedEvent {
+    MouseMove {
+        x: f32,
+        y: f32,
+    },
+    MouseButton {
+        action: ButtonAction,
+        /// `left`, `middle` or `right`.
+        button: String,
+        x: f32,
+        y: f32,
+    },
+    Wheel {
+        dx: f64,
+        dy: f64,
+        dz: f64,
+        /// `pixel`, `line` or `page`.
+        unit: String,
+        x: f32,
+        y: f32,
+    },
+    Scroll {
+        dx: f32,
+        dy: f32,
+        x: i32,
+        y: i32,
+        phase: Phase,
+    },
+    Touch {
+        phase: Phase,
+        id: i32,
+        x: f32,
+        y: f32,
+    },
+    Key {
+        down: bool,
+        /// The key's value as `keyboard_types` names it, e.g. `a` or
+        /// `ArrowLeft`.
+        key: String,
+        /// The physical key, e.g. `KeyA`.
+        code: String,
+        /// `standard`, `left`, `right` or `numpad`.
+        location: String,
+        modifiers: u32,
+        repeat: bool,
+        is_composing: bool,
+    },
+    Composition {
+        /// `start`, `update` or `end`.
+        state: String,
+        data: String,
+    },
+    ImeDismissed,
+    Zoom {
+        magnification: f32,
+    },
+    ResetZoom,
+    PinchZoom {
+        magnification: f32,
+    },
+    Back,
+    Forward,
+    Reload,
+    /// The window's drawable area changed size or density.
+    Resize {
+        width: i32,
+        height: i32,
+        scale_factor: f32,
+    },
+    /// A point at which a replay takes a screenshot.
+    Checkpoint,
+    Quit,
+}
+
+impl RecordedEvent {
+    /// The recorded form of an event for Servo, if it is one that gets
+    /// recorded. Resizes are recorded separately, from the window's events.
+    pub fn from_embedder_event(event: &EmbedderEvent) -> Option<RecordedEvent> {
+        let button_name = |button: &MouseButton| {
+            match button {
+                MouseButton::Left => "left",
+                MouseButton::Middle => "middle",
+                MouseButton::Right => "right",
+            }
+            .to_owned()
+        };
+        Some(match event {
+            EmbedderEvent::MouseWindowMoveEventClass(point) => RecordedEvent::MouseMove {
+                x: point.x,
+                y: point.y,
+            },
+            EmbedderEvent::MouseWindowEventClass(event) => {
+                let (action, button, point) = match event {
+                    MouseWindowEvent::MouseDown(button, point) => (ButtonAction::Down, button, point),
+                    MouseWindowEvent::MouseUp(button, point) => (ButtonAction::Up, button, point),
+                    MouseWindowEvent::Click(button, point) => (ButtonAction::Click, button, point),
+                };
+                RecordedEvent::MouseButton {
+                    action,
+                    button: button_name(button),
+                    x: point.x,
+                    y: point.y,
+                }
+            }
+            EmbedderEvent::Wheel(delta, point) => RecordedEvent::Wheel {
+                dx: delta.x,
+                dy: delta.y,
+                dz: delta.z,
+                unit: match delta.mode {
+                    WheelMode::DeltaPixel => "pixel",
+                    WheelMode::DeltaLine => "line",
+                    WheelMode::DeltaPage => "page",
+                }
+                .to_owned(),
+                x: point.x,
+                y: point.y,
+            },
+            EmbedderEvent::Scroll(ScrollLocation::Delta(delta), point, phase) => {
+                RecordedEvent::Scroll {
+                    dx: delta.x,
+                    dy: delta.y,
+                    x: point.x,
+                    y: point.y,
+                    phase: (*phase).into(),
+                }
+            }
+            EmbedderEvent::Touch(phase, TouchId(id), point) => RecordedEvent::Touch {
+                phase: (*phase).into(),
+                id: *id,
+                x: point.x,
+                y: point.y,
+            },
+            EmbedderEvent::Keyboard(event) => RecordedEvent::Key {
+                down: event.state == KeyState::Down,
+                key: event.key.to_string(),
+                code: event.code.to_string(),
+                location: match event.location {
+                    Location::Standard => "standard",
+                    Location::Left => "left",
+                    Location::Right => "right",
+                    Location::Numpad => "numpad",
+                }
+                .to_owned(),
+                modifiers: event.modifiers.bits(),
+                repeat: event.repeat,
+                is_composing: event.is_composing,
+            },
+            EmbedderEvent::IMEComposition(event) => RecordedEvent::Composition {
+                state: match event.state {
+                    CompositionState::Start => "start",
+                    CompositionState::Update => "update",
+                    CompositionState::End => "end",
+                }
+                .to_owned(),
+                data: event.data.clone(),
+            },
+            EmbedderEvent::IMEDismissed => RecordedEvent::ImeDismissed,
+            EmbedderEvent::Zoom(magnification) => RecordedEvent::Zoom {
+                magnification: *magnification,
+            },
+            EmbedderEvent::ResetZoom => RecordedEvent::ResetZoom,
+            EmbedderEvent::PinchZoom(magnification) => RecordedEvent::PinchZoom {
+                magnification: *magnification,
+            },
+            EmbedderEvent::Navigation(_, TraversalDirection::Back(_)) => RecordedEvent::Back,
+            EmbedderEvent::Navigation(_, TraversalDirection::Forward(_)) => RecordedEvent::Forward,
+            EmbedderEvent::Reload(_) => RecordedEvent::Reload,
+            EmbedderEvent::Quit => RecordedEvent::Quit,
+            _ => return None,
+        })
+    }
+
+    /// The event to hand Servo when replaying this one, addressed to
+    /// `webview_id` where that matters. `None` for resizes and checkpoints,
+    /// which the embedder carries out itself, and for anything that no
+    /// longer parses.
+    pub fn to_embedder_event(&self, webview_id: WebViewId) -> Option<EmbedderEvent> {
+        let button = |name: &str| match name {
+            "left" => Some(MouseButton::Left),
+            "middle" => Some(MouseButton::Middle),
+            "right" => Some(MouseButton::Right),
+            _ => None,
+        };
+        Some(match self {
+            RecordedEvent::MouseMove { x, y } => {
+                EmbedderEvent::MouseWindowMoveEventClass(DevicePoint::new(*x, *y))
+            }
+            RecordedEvent::MouseButton { action, button: name, x, y } => {
+                let button = button(name)?;
+                let point = DevicePoint::new(*x, *y);
+                EmbedderEvent::MouseWindowEventClass(match action {
+                    ButtonAction::Down => MouseWindowEvent::MouseDown(button, point),
+                    ButtonAction::Up => MouseWindowEvent::MouseUp(button, point),
+                    ButtonAction::Click => MouseWindowEvent::Click(button, point),
+                })
+            }
+            RecordedEvent::Wheel { dx, dy, dz, unit, x, y } => {
+                let mode = match unit.as_str() {
+                    "pixel" => WheelMode::DeltaPixel,
+                    "line" => WheelMode::DeltaLine,
+                    "page" => WheelMode::DeltaPage,
+                    _ => return None,
+                };
+                let delta = WheelDelta { x: *dx, y: *dy, z: *dz, mode };
+                EmbedderEvent::Wheel(delta, DevicePoint::new(*x, *y))
+            }
+            RecordedEvent::Scroll { dx, dy, x, y, phase } => EmbedderEvent::Scroll(
+                ScrollLocation::Delta(LayoutVector2D::new(*dx, *dy)),
+                DeviceIntPoint::new(*x, *y),
+                (*phase).into(),
+            ),
+            RecordedEvent::Touch { phase, id, x, y } => {
+                EmbedderEvent::Touch((*phase).into(), TouchId(*id), DevicePoint::new(*x, *y))
+            }
+            RecordedEvent::Key {
+                down,
+                key,
+                code,
+                location,
+                modifiers,
+                repeat,
+                is_composing,
+            } => EmbedderEvent::Keyboard(KeyboardEvent {
+                state: if *down { KeyState::Down } else { KeyState::Up },
+                key: Key::from_str(key).ok()?,
+                code: Code::from_str(code).unwrap_or(Code::Unidentified),
+                location: match location.as_str() {
+                    "left" => Location::Left,
+                    "right" => Location::Right,
+                    "numpad" => Location::Numpad,
+                    _ => Location::Standard,
+                },
+                modifiers: Modifiers::from_bits_truncate(*modifiers),
+                repeat: *repeat,
+                is_composing: *is_composing,
+            }),
+            RecordedEvent::Composition { state, data } => {
+                let state = match state.as_str() {
+                    "start" => CompositionState::Start,
+                    "update" => CompositionState::Update,
+                    "end" => CompositionState::End,
+                    _ => return None,
+                };
+                EmbedderEvent::IMEComposition(CompositionEvent {
+                    state,
+                    data: data.clone(),
+                })
+            }
+            RecordedEvent::ImeDismissed => EmbedderEvent::IMEDismissed,
+            RecordedEvent::Zoom { magnification } => EmbedderEvent::Zoom(*magnification),
+            RecordedEvent::ResetZoom => EmbedderEvent::ResetZoom,
+            RecordedEvent::PinchZoom { magnification } => EmbedderEvent::PinchZoom(*magnification),
+            RecordedEvent::Back => {
+                EmbedderEvent::Navigation(webview_id, TraversalDirection::Back(1))
+            }
+            RecordedEvent::Forward => {
+                EmbedderEvent::Navigation(webview_id, TraversalDirection::Forward(1))
+            }
+            RecordedEvent::Reload => EmbedderEvent::Reload(webview_id),
+            RecordedEvent::Quit => EmbedderEvent::Quit,
+            RecordedEvent::Resize { .. } | RecordedEvent::Checkpoint => return None,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Entry {
+    /// Seconds since the page first finished loading.
+    pub time: f64,
+    #[serde(flatten)]
+    pub event: RecordedEvent,
+}
+
+/// The clock recordings and replays keep time with, which starts when the
+/// page first finishes loading.
+#[derive(Default)]
+struct LoadClock {
+    loaded: Option<Instant>,
+}
+
+impl LoadClock {
+    fn load_complete(&mut self) {
+        self.loaded.get_or_insert_with(Instant::now);
+    }
+
+    fn now(&self) -> f64 {
+        self.loaded.map_or(0.0, |loaded| loaded.elapsed().as_secs_f64())
+    }
+}
+
+/// Writes a recording as events happen.
+pub(crate) struct Recorder {
+    file: LineWriter<File>,
+    clock: LoadClock,
+}
+
+impl Recorder {
+    pub fn create(path: &Path, header: &Header) -> io::Result<Recorder> {
+        let mut file = LineWriter::new(File::create(path)?);
+        writeln!(file, "{}", serde_json::to_string(header)?)?;
+        Ok(Recorder {
+            file,
+            clock: LoadClock::default(),
+        })
+    }
+
+    pub fn load_complete(&mut self) {
+        self.clock.load_complete();
+    }
+
+    pub fn record(&mut self, event: RecordedEvent) {
+        let entry = Entry {
+            time: self.clock.now(),
+            event,
+        };
+        let result = serde_json::to_string(&entry)
+            .map_err(io::Error::from)
+            .and_then(|line| writeln!(self.file, "{}", line));
+        if let Err(error) = result {
+            log::warn!("Failed to record {:?}: {}", entry.event, error);
+        }
+    }
+}
+
+/// Reads the header of the recording at `path`.
+pub fn read_header(path: &Path) -> io::Result<Header> {
+    let mut line = String::new();
+    BufReader::new(File::open(path)?).read_line(&mut line)?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+/// Hands out a recording's events as their time comes.
+pub(crate) struct Replayer {
+    pub header: Header,
+    entries: VecDeque<Entry>,
+    clock: LoadClock,
+}
+
+impl Replayer {
+    pub fn open(path: &Path) -> io::Result<Replayer> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let header = lines
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "empty recording"))??;
+        let header: Header = serde_json::from_str(&header)?;
+        let mut entries = VecDeque::new();
+        for line in lines {
+            let line = line?;
+            if !line.trim().is_empty() {
+                entries.push_back(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(Replayer {
+            header,
+            entries,
+            clock: LoadClock::default(),
+        })
+    }
+
+    pub fn load_complete(&mut self) {
+        self.clock.load_complete();
+    }
+
+    /// Takes the events whose time has come. Nothing is due before the
+    /// page has loaded.
+    pub fn due_events(&mut self) -> Vec<RecordedEvent> {
+        if self.clock.loaded.is_none() {
+            return vec![];
+        }
+        let now = self.clock.now();
+        let mut due = vec![];
+        while self.entries.front().is_some_and(|entry| entry.time <= now) {
+            due.extend(self.entries.pop_front().map(|entry| entry.event));
+        }
+        due
+    }
+
+    /// When the next event is due, if the clock has started.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let loaded = self.clock.loaded?;
+        let entry = self.entries.front()?;
+        Some(loaded + Duration::from_secs_f64(entry.time.max(0.0)))
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Saves RGBA `pixels`, stored top row first, as a PNG file, creating the
+/// directory it goes in if needed.
+pub(crate) fn save_screenshot(path: &Path, pixels: &[u8], size: DeviceIntSize) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, size.width as u32, size.height as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(pixels))
+        .map_err(|error| io::Error::new(io::ErrorKind::Other, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn to_line(entry: &Entry) -> String {
+        serde_json::to_string(entry).unwrap()
+    }
+
+    fn parse(line: &str) -> serde_json::Result<Entry> {
+        serde_json::from_str(line)
+    }
+
+    /// Writes `contents` to a file of its own in the temporary directory.
+    fn write_recording(name: &str, contents: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("lepton-{}-{}.jsonl", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    const HEADER: &str = r#"{"url":"about:blank","width":800,"height":600,"scale_factor":2.0}"#;
+
+    #[test]
+    fn entries_survive_a_round_trip() {
+        let events = vec![
+            RecordedEvent::MouseMove { x: 1.5, y: 2.0 },
+            RecordedEvent::MouseButton {
+                action: ButtonAction::Click,
+                button: "right".to_owned(),
+                x: 3.0,
+                y: 4.0,
+            },
+            RecordedEvent::Wheel {
+                dx: 0.0,
+                dy: -38.0,
+                dz: 0.0,
+                unit: "line".to_owned(),
+                x: 5.0,
+                y: 6.0,
+            },
+            RecordedEvent::Scroll {
+                dx: 0.0,
+                dy: -38.0,
+                x: 5,
+                y: 6,
+                phase: Phase::Move,
+            },
+            RecordedEvent::Touch {
+                phase: Phase::Cancel,
+                id: 1,
+                x: 7.0,
+                y: 8.0,
+            },
+            RecordedEvent::Key {
+                down: true,
+                key: "ArrowLeft".to_owned(),
+                code: "ArrowLeft".to_owned(),
+                location: "standard".to_owned(),
+                modifiers: 8,
+                repeat: false,
+                is_composing: false,
+            },
+            RecordedEvent::Composition {
+                state: "update".to_owned(),
+                data: "日本".to_owned(),
+            },
+            RecordedEvent::Zoom { magnification: 1.1 },
+            RecordedEvent::Resize {
+                width: 640,
+                height: 480,
+                scale_factor: 1.0,
+            },
+            RecordedEvent::Checkpoint,
+            RecordedEvent::Quit,
+        ];
+        for (index, event) in events.into_iter().enumerate() {
+            let entry = Entry {
+                time: index as f64 * 0.25,
+                event,
+            };
+            let line = to_line(&entry);
+            let parsed = parse(&line).unwrap_or_else(|error| panic!("{}: {}", line, error));
+            assert_eq!(to_line(&parsed), line);
+            assert_eq!(parsed.time, entry.time);
+        }
+    }
+
+    #[test]
+    fn entries_are_flat_json_objects() {
+        let entry = parse(
+            r#"{"time":1.5,"type":"mouse-button","action":"down","button":"left","x":1,"y":2}"#,
+        )
+        .unwrap();
+        assert_eq!(entry.time, 1.5);
+        assert!(matches!(
+            entry.event,
+            RecordedEvent::MouseButton {
+                action: ButtonAction::Down,
+                ref button,
+                x,
+                y,
+            } if button == "left" && x == 1.0 && y == 2.0
+        ));
+        let line = to_line(&Entry {
+            time: 0.0,
+            event: RecordedEvent::ImeDismissed,
+        });
+        assert_eq!(line, r#"{"time":0.0,"type":"ime-dismissed"}"#);
+    }
+
+    #[test]
+    fn malformed_entries_are_rejected() {
+        let lines = [
+            "",
+            "not json",
+            r#"{"type":"quit"}"#,
+            r#"{"time":"soon","type":"quit"}"#,
+            r#"{"time":1.0}"#,
+            r#"{"time":1.0,"type":"teleport"}"#,
+            r#"{"time":1.0,"type":"mouse-move","x":1.0}"#,
+            r#"{"time":1.0,"type":"touch","phase":"hover","id":0,"x":0,"y":0}"#,
+            r#"{"time":1.0,"type":"mouse-button","action":"press","button":"left","x":0,"y":0}"#,
+        ];
+        for line in lines.iter() {
+            assert!(parse(line).is_err(), "{:?} was accepted", line);
+        }
+    }
+
+    #[test]
+    fn headers_survive_a_round_trip() {
+        let header: Header = serde_json::from_str(HEADER).unwrap();
+        assert_eq!(header.size(), DeviceIntSize::new(800, 600));
+        assert_eq!(serde_json::to_string(&header).unwrap(), HEADER);
+    }
+
+    #[test]
+    fn replays_skip_blank_lines() {
+        let path = write_recording(
+            "blank-lines",
+            &format!(
+                "{}\n{}\n\n{}\n",
+                HEADER, r#"{"time":0.5,"type":"reload"}"#, r#"{"time":1.0,"type":"quit"}"#
+            ),
+        );
+        let replayer = Replayer::open(&path);
+        std::fs::remove_file(&path).unwrap();
+        let replayer = replayer.unwrap();
+        assert_eq!(replayer.header.url, "about:blank");
+        assert_eq!(replayer.entries.len(), 2);
+        assert!(!replayer.is_finished());
+    }
+
+    #[test]
+    fn replays_reject_malformed_recordings() {
+        let recordings = [
+            ("empty", String::new()),
+            ("bad-header", format!("{}\n", r#"{"url":"about:blank"}"#)),
+            (
+                "bad-entry",
+                format!("{}\n{}\n", HEADER, r#"{"time":0.5,"type":"jump"}"#),
+            ),
+        ];
+        for (name, contents) in recordings.iter() {
+            let path = write_recording(name, contents);
+            let result = Replayer::open(&path);
+            std::fs::remove_file(&path).unwrap();
+            assert!(result.is_err(), "the {} recording was accepted", name);
+        }
+    }
+
+    #[test]
+    fn nothing_is_due_before_the_page_loads() {
+        let path = write_recording(
+            "unloaded",
+            &format!("{}\n{}\n", HEADER, r#"{"time":-1.0,"type":"reload"}"#),
+        );
+        let replayer = Replayer::open(&path);
+        std::fs::remove_file(&path).unwrap();
+        let mut replayer = replayer.unwrap();
+        assert!(replayer.due_events().is_empty());
+        assert_eq!(replayer.next_deadline(), None);
+        replayer.load_complete();
+        assert!(matches!(replayer.due_events()[..], [RecordedEvent::Reload]));
+        assert!(replayer.is_finished());
+    }
+}
@@ -0,0 +1,271 @@
+//! Deciding when to draw frames. However many reasons to redraw come up in
+//! between (Servo rendering, animation ticks, resizes, menus changing), the
+//! window is drawn at most once per display refresh, or less often when a
+//! frame rate cap is set.
+
+use std::time::{Duration, Instant};
+
+use crate::backend::{LoopControl, WindowBackend};
+
+/// What to assume when the backend can't tell the display's refresh rate.
+pub const DEFAULT_REFRESH_RATE: f32 = 60.0;
+
+/// Counts of what became of the frames asked for since startup.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameStats {
+    /// Frames drawn to the window.
+    pub presented: u64,
+    /// Requests for a frame that were folded into one already asked for.
+    pub coalesced: u64,
+    /// Frames drawn without Servo having rendered anything new, because the
+    /// window needed repainting or one of lepton's menus changed.
+    pub duplicates: u64,
+    /// Frame slots missed while the page was animating.
+    pub dropped: u64,
+}
+
+pub(crate) struct FrameScheduler {
+    max_fps: Option<f32>,
+    /// The shortest time between two frames.
+    interval: Duration,
+    last_frame: Option<Instant>,
+    /// When Servo was last woken up to advance its animations.
+    last_tick: Option<Instant>,
+    /// A frame has been asked for and not drawn yet.
+    pending: bool,
+    /// The backend has been asked for a redraw, which hasn't come yet.
+    redraw_requested: bool,
+    /// Servo has rendered something that hasn't been presented.
+    new_frame: bool,
+    stats: FrameStats,
+}
+
+impl FrameScheduler {
+    pub fn new(max_fps: Option<f32>, refresh_rate: Option<f32>) -> FrameScheduler {
+        let mut scheduler = FrameScheduler {
+            max_fps,
+            interval: Duration::ZERO,
+            last_frame: None,
+            last_tick: None,
+            pending: false,
+            redraw_requested: false,
+            new_frame: false,
+            stats: FrameStats::default(),
+        };
+        scheduler.set_refresh_rate(refresh_rate);
+        scheduler
+    }
+
+    /// Paces frames to a display refreshing `rate` times a second, for
+    /// instance after the window moved to another monitor.
+    pub fn set_refresh_rate(&mut self, rate: Option<f32>) {
+        let rate = rate.filter(|rate| *rate > 0.0).unwrap_or(DEFAULT_REFRESH_RATE);
+        let fps = match self.max_fps {
+            Some(max_fps) => max_fps.min(rate),
+            None => rate,
+        };
+        self.interval = Duration::from_secs_f32(1.0 / fps);
+    }
+
+    /// Asks for a frame to be drawn, because Servo rendered one when
+    /// `new_frame` is set, or because something else changed.
+    pub fn request_frame(&mut self, new_frame: bool) {
+        self.new_frame |= new_frame;
+        if self.pending {
+            self.stats.coalesced += 1;
+        }
+        self.pending = true;
+    }
+
+    /// Records that a frame is being drawn, and returns whether Servo has
+    /// rendered something since the last one, which has to be presented
+    /// before it can be drawn.
+    pub fn start_frame(&mut self, animating: bool) -> bool {
+        self.start_frame_at(Instant::now(), animating)
+    }
+
+    fn start_frame_at(&mut self, now: Instant, animating: bool) -> bool {
+        if !self.new_frame {
+            self.stats.duplicates += 1;
+        }
+        if let Some(last_frame) = self.last_frame.filter(|_| animating) {
+            let slots = now.duration_since(last_frame).as_secs_f32() / self.interval.as_secs_f32();
+            // Allow for some jitter before counting a slot as missed.
+            if slots > 1.5 {
+                self.stats.dropped += slots.round() as u64 - 1;
+            }
+        }
+        self.stats.presented += 1;
+        self.last_frame = Some(now);
+        self.pending = false;
+        self.redraw_requested = false;
+        std::mem::take(&mut self.new_frame)
+    }
+
+    /// Records that Servo was woken up to advance its animations.
+    pub fn animation_ticked(&mut self) {
+        self.last_tick = Some(Instant::now());
+    }
+
+    /// Asks the backend for a redraw if a frame is due, and tells the event
+    /// loop how long it may sleep. While the page is animating, the loop is
+    /// woken up once per frame so Servo can advance its animations.
+    pub fn poll(&mut self, backend: &dyn WindowBackend, animating: bool) -> LoopControl {
+        match self.next_step(Instant::now(), animating) {
+            Some(control) => control,
+            None => {
+                backend.request_redraw();
+                self.redraw_requested = true;
+                LoopControl::Wait
+            }
+        }
+    }
+
+    /// What the event loop should do at `now`, or `None` if a redraw has to
+    /// be asked for.
+    fn next_step(&self, now: Instant, animating: bool) -> Option<LoopControl> {
+        if self.redraw_requested || !(self.pending || animating) {
+            return Some(LoopControl::Wait);
+        }
+        let next_slot = |since: Option<Instant>| since.map_or(now, |since| since + self.interval);
+        if !self.pending {
+            let due = next_slot(self.last_frame.max(self.last_tick));
+            return Some(LoopControl::WaitUntil(due.max(now)));
+        }
+        let due = next_slot(self.last_frame);
+        (due > now).then_some(LoopControl::WaitUntil(due))
+    }
+
+    pub fn stats(&self) -> FrameStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sixty_fps() -> FrameScheduler {
+        FrameScheduler::new(None, Some(60.0))
+    }
+
+    #[test]
+    fn an_idle_window_waits() {
+        let scheduler = sixty_fps();
+        assert_eq!(
+            scheduler.next_step(Instant::now(), false),
+            Some(LoopControl::Wait)
+        );
+    }
+
+    #[test]
+    fn the_first_frame_is_due_at_once() {
+        let mut scheduler = sixty_fps();
+        scheduler.request_frame(true);
+        assert_eq!(scheduler.next_step(Instant::now(), false), None);
+    }
+
+    #[test]
+    fn frames_wait_for_the_next_slot() {
+        let mut scheduler = sixty_fps();
+        let start = Instant::now();
+        scheduler.request_frame(true);
+        scheduler.start_frame_at(start, false);
+        scheduler.request_frame(true);
+        let due = start + scheduler.interval;
+        let soon = start + Duration::from_millis(1);
+        assert_eq!(
+            scheduler.next_step(soon, false),
+            Some(LoopControl::WaitUntil(due))
+        );
+        assert_eq!(scheduler.next_step(due, false), None);
+    }
+
+    #[test]
+    fn a_requested_redraw_is_not_requested_again() {
+        let mut scheduler = sixty_fps();
+        scheduler.request_frame(true);
+        scheduler.redraw_requested = true;
+        assert_eq!(
+            scheduler.next_step(Instant::now(), true),
+            Some(LoopControl::Wait)
+        );
+    }
+
+    #[test]
+    fn the_frame_rate_cap_throttles_below_the_refresh_rate() {
+        let scheduler = FrameScheduler::new(Some(30.0), Some(60.0));
+        assert_eq!(scheduler.interval, Duration::from_secs_f32(1.0 / 30.0));
+        let scheduler = FrameScheduler::new(Some(120.0), Some(60.0));
+        assert_eq!(scheduler.interval, Duration::from_secs_f32(1.0 / 60.0));
+    }
+
+    #[test]
+    fn unknown_refresh_rates_are_assumed() {
+        let mut scheduler = FrameScheduler::new(None, None);
+        assert_eq!(
+            scheduler.interval,
+            Duration::from_secs_f32(1.0 / DEFAULT_REFRESH_RATE)
+        );
+        scheduler.set_refresh_rate(Some(0.0));
+        assert_eq!(
+            scheduler.interval,
+            Duration::from_secs_f32(1.0 / DEFAULT_REFRESH_RATE)
+        );
+        scheduler.set_refresh_rate(Some(120.0));
+        assert_eq!(scheduler.interval, Duration::from_secs_f32(1.0 / 120.0));
+    }
+
+    #[test]
+    fn animations_tick_once_per_slot() {
+        let mut scheduler = sixty_fps();
+        let start = Instant::now();
+        scheduler.start_frame_at(start, true);
+        let tick = start + Duration::from_millis(5);
+        scheduler.last_tick = Some(tick);
+        assert_eq!(
+            scheduler.next_step(start, true),
+            Some(LoopControl::WaitUntil(tick + scheduler.interval))
+        );
+        // A late tick is due straight away.
+        let late = tick + 2 * scheduler.interval;
+        assert_eq!(
+            scheduler.next_step(late, true),
+            Some(LoopControl::WaitUntil(late))
+        );
+    }
+
+    #[test]
+    fn requests_in_between_frames_are_coalesced() {
+        let mut scheduler = sixty_fps();
+        scheduler.request_frame(true);
+        scheduler.request_frame(false);
+        scheduler.request_frame(true);
+        assert!(scheduler.start_frame_at(Instant::now(), false));
+        assert_eq!(scheduler.stats().coalesced, 2);
+        assert_eq!(scheduler.stats().presented, 1);
+        assert_eq!(scheduler.stats().duplicates, 0);
+    }
+
+    #[test]
+    fn frames_without_new_content_are_duplicates() {
+        let mut scheduler = sixty_fps();
+        scheduler.request_frame(false);
+        assert!(!scheduler.start_frame_at(Instant::now(), false));
+        assert_eq!(scheduler.stats().duplicates, 1);
+    }
+
+    #[test]
+    fn missed_slots_count_as_dropped_while_animating() {
+        let mut scheduler = sixty_fps();
+        let start = Instant::now();
+        scheduler.start_frame_at(start, true);
+        scheduler.start_frame_at(start + scheduler.interval, true);
+        assert_eq!(scheduler.stats().dropped, 0);
+        scheduler.start_frame_at(start + 4 * scheduler.interval, true);
+        assert_eq!(scheduler.stats().dropped, 2);
+        // Nothing is dropped while the page is still.
+        scheduler.start_frame_at(start + 10 * scheduler.interval, false);
+        assert_eq!(scheduler.stats().dropped, 2);
+    }
+}
@@ -0,0 +1,198 @@
+//! Running scripts in a page on behalf of the embedding application, and
+//! getting their results back as JSON.
+//!
+//! Scripts are evaluated through Servo's WebDriver script command, which
+//! answers over IPC from the page's script thread. The answers are picked up
+//! by ipc-channel's router thread, which wakes up the event loop so the
+//! callback runs on the embedder's thread with the `App` at hand. Evaluations
+//! that get no answer in time fail, rather than waiting forever.
+//!
+//! The source is evaluated in the page's global scope, and its completion
+//! value is serialized in the page with `JSON.stringify`. Exceptions are
+//! caught there too, so their message can be passed on.
+
+use ipc_channel::ipc;
+use ipc_channel::router::ROUTER;
+use serde_json::Value;
+use servo::script_traits::webdriver_msg::{WebDriverJSResult, WebDriverJSValue, WebDriverScriptCommand};
+use servo::EventLoopWaker;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+use crate::app::App;
+
+/// Why evaluating a script gave no result.
+#[derive(Clone, Debug, PartialEq)]
+pub enum JavaScriptError {
+    /// The script threw, or its result couldn't be serialized, for instance
+    /// because it refers to itself. Holds what was thrown, as a string.
+    Exception(String),
+    /// The page couldn't run the script, for instance because the webview
+    /// is gone or hasn't loaded anything yet.
+    Failed(String),
+    /// Servo gave no answer, because it shut down, there was no page to
+    /// ask, for instance before the webview has been created, or the page
+    /// took longer than `SCRIPT_TIMEOUT` to run the script.
+    NoAnswer,
+}
+
+impl fmt::Display for JavaScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JavaScriptError::Exception(message) => write!(f, "Uncaught {}", message),
+            JavaScriptError::Failed(reason) => write!(f, "Failed to run script: {}", reason),
+            JavaScriptError::NoAnswer => write!(f, "Servo didn't run the script"),
+        }
+    }
+}
+
+impl std::error::Error for JavaScriptError {}
+
+/// What a script evaluated to. `undefined`, functions and other values JSON
+/// has no room for come out as `null`.
+pub type JavaScriptResult = Result<Value, JavaScriptError>;
+
+/// Called with the result of `App::evaluate_javascript`.
+pub type JavaScriptCallback = Box<dyn FnOnce(&mut App, JavaScriptResult)>;
+
+/// How long a page gets to run a script, the same as WebDriver's default
+/// script timeout.
+pub const SCRIPT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The script actually sent to the page, which evaluates `source` and
+/// reports its value or exception as a JSON string.
+fn wrap_source(source: &str) -> String {
+    // A JSON string is also a JavaScript string literal. Indirect `eval`
+    // runs the source in the global scope, as a classic script would.
+    let literal = Value::String(source.to_owned());
+    format!(
+        "(function () {{\n\
+         try {{ return JSON.stringify({{ value: (0, eval)({}) }}); }}\n\
+         catch (error) {{ return JSON.stringify({{ exception: String(error) }}); }}\n\
+         }})()",
+        literal
+    )
+}
+
+fn parse_result(result: WebDriverJSResult) -> JavaScriptResult {
+    let json = match result {
+        Ok(WebDriverJSValue::String(json)) => json,
+        Ok(value) => return Err(JavaScriptError::Failed(format!("unexpected result {:?}", value))),
+        Err(error) => return Err(JavaScriptError::Failed(format!("{:?}", error))),
+    };
+    let mut outcome: Value = serde_json::from_str(&json)
+        .map_err(|error| JavaScriptError::Failed(format!("invalid result: {}", error)))?;
+    if let Some(exception) = outcome.get("exception") {
+        return Err(JavaScriptError::Exception(
+            exception.as_str().unwrap_or_default().to_owned(),
+        ));
+    }
+    // `JSON.stringify` leaves out properties that are undefined.
+    Ok(outcome.get_mut("value").map(Value::take).unwrap_or(Value::Null))
+}
+
+/// Passes an evaluation's answer on to the event loop, or the lack of one if
+/// Servo drops its end of the channel without answering.
+struct Reply {
+    id: u64,
+    sender: Sender<(u64, JavaScriptResult)>,
+    waker: Box<dyn EventLoopWaker>,
+    answered: bool,
+}
+
+impl Reply {
+    fn send(&mut self, result: JavaScriptResult) {
+        self.answered = true;
+        if self.sender.send((self.id, result)).is_ok() {
+            self.waker.wake();
+        }
+    }
+}
+
+impl Drop for Reply {
+    fn drop(&mut self) {
+        if !self.answered {
+            self.send(Err(JavaScriptError::NoAnswer));
+        }
+    }
+}
+
+/// Keeps track of the evaluations waiting for an answer.
+pub(crate) struct ScriptEvaluator {
+    next_id: u64,
+    /// The callbacks waiting for an answer, with when to stop waiting.
+    callbacks: HashMap<u64, (JavaScriptCallback, Instant)>,
+    sender: Sender<(u64, JavaScriptResult)>,
+    receiver: Receiver<(u64, JavaScriptResult)>,
+    waker: Box<dyn EventLoopWaker>,
+}
+
+impl ScriptEvaluator {
+    pub fn new(waker: Box<dyn EventLoopWaker>) -> ScriptEvaluator {
+        let (sender, receiver) = mpsc::channel();
+        ScriptEvaluator {
+            next_id: 0,
+            callbacks: HashMap::new(),
+            sender,
+            receiver,
+            waker,
+        }
+    }
+
+    /// Starts waiting for the result of evaluating `source`, and returns
+    /// the command that asks the page for it.
+    pub fn evaluate(&mut self, source: &str, callback: JavaScriptCallback) -> WebDriverScriptCommand {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.callbacks.insert(id, (callback, Instant::now() + SCRIPT_TIMEOUT));
+
+        let (result_sender, result_receiver) = ipc::channel().expect("Failed to create IPC channel");
+        let mut reply = Reply {
+            id,
+            sender: self.sender.clone(),
+            waker: self.waker.clone_box(),
+            answered: false,
+        };
+        ROUTER.add_typed_route(
+            result_receiver,
+            Box::new(move |result| {
+                reply.send(match result {
+                    Ok(result) => parse_result(result),
+                    Err(error) => Err(JavaScriptError::Failed(format!("invalid answer: {}", error))),
+                });
+            }),
+        );
+        WebDriverScriptCommand::ExecuteScript(wrap_source(source), result_sender)
+    }
+
+    /// Takes the evaluations that have been answered or timed out, with
+    /// their callbacks.
+    pub fn take_finished(&mut self) -> Vec<(JavaScriptCallback, JavaScriptResult)> {
+        let answered: Vec<_> = self.receiver.try_iter().collect();
+        let mut finished: Vec<_> = answered
+            .into_iter()
+            .filter_map(|(id, result)| Some((self.callbacks.remove(&id)?.0, result)))
+            .collect();
+        let now = Instant::now();
+        let timed_out: Vec<u64> = self
+            .callbacks
+            .iter()
+            .filter(|(_, (_, deadline))| *deadline <= now)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in timed_out {
+            if let Some((callback, _)) = self.callbacks.remove(&id) {
+                finished.push((callback, Err(JavaScriptError::NoAnswer)));
+            }
+        }
+        finished
+    }
+
+    /// When the evaluation that has waited longest times out, if any are
+    /// waiting.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.callbacks.values().map(|(_, deadline)| *deadline).min()
+    }
+}
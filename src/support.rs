@@ -1,76 +1,274 @@
-use euclid::default::Size2D;
-use glutin::{self, PossiblyCurrent};
+use euclid::default::{Box2D, Point2D, Size2D};
 
 use std::ffi::CStr;
+use std::fmt;
+use std::os::raw::c_void;
+
+use crate::logging;
+use crate::quad::{Quad, QuadRenderer};
 
 pub mod gl {
     pub use self::Gles2 as Gl;
     include!(concat!(env!("OUT_DIR"), "/gl_bindings.rs"));
 }
 
+use self::gl::types::{GLchar, GLenum, GLsizei, GLuint};
+
+/// A GL call that failed, as reported by `glGetError`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlError {
+    pub call: &'static str,
+    pub code: GLenum,
+}
+
+impl fmt::Display for GlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self.code {
+            gl::INVALID_ENUM => "GL_INVALID_ENUM",
+            gl::INVALID_VALUE => "GL_INVALID_VALUE",
+            gl::INVALID_OPERATION => "GL_INVALID_OPERATION",
+            gl::INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION",
+            gl::OUT_OF_MEMORY => "GL_OUT_OF_MEMORY",
+            _ => return write!(f, "{} failed with GL error {:#x}", self.call, self.code),
+        };
+        write!(f, "{} failed with {}", self.call, name)
+    }
+}
+
+impl std::error::Error for GlError {}
+
 pub struct Gl {
     pub gl: gl::Gl,
-    fb: u32,
+    quad: QuadRenderer,
+    /// Holds the pixels of overlays while they are drawn.
+    overlay_texture: u32,
+}
+
+fn debug_source(source: GLenum) -> &'static str {
+    match source {
+        gl::DEBUG_SOURCE_API => "api",
+        gl::DEBUG_SOURCE_WINDOW_SYSTEM => "window system",
+        gl::DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
+        gl::DEBUG_SOURCE_THIRD_PARTY => "third party",
+        gl::DEBUG_SOURCE_APPLICATION => "application",
+        _ => "other",
+    }
 }
 
-pub fn load(gl_context: &glutin::Context<PossiblyCurrent>) -> Gl {
-    let gl =
-        gl::Gl::load_with(|ptr| gl_context.get_proc_address(ptr) as *const _);
+fn debug_type(message_type: GLenum) -> &'static str {
+    match message_type {
+        gl::DEBUG_TYPE_ERROR => "error",
+        gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated behavior",
+        gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behavior",
+        gl::DEBUG_TYPE_PORTABILITY => "portability",
+        gl::DEBUG_TYPE_PERFORMANCE => "performance",
+        gl::DEBUG_TYPE_MARKER => "marker",
+        _ => "other",
+    }
+}
 
-    let version = unsafe {
-        let data = CStr::from_ptr(gl.GetString(gl::VERSION) as *const _)
-            .to_bytes()
-            .to_vec();
-        String::from_utf8(data).unwrap()
+extern "system" fn debug_message_callback(
+    source: GLenum,
+    message_type: GLenum,
+    id: GLuint,
+    severity: GLenum,
+    length: GLsizei,
+    message: *const GLchar,
+    _user_param: *mut c_void,
+) {
+    let message = unsafe {
+        if length < 0 {
+            CStr::from_ptr(message).to_string_lossy()
+        } else {
+            let bytes = std::slice::from_raw_parts(message as *const u8, length as usize);
+            String::from_utf8_lossy(bytes)
+        }
+    };
+    let level = match severity {
+        gl::DEBUG_SEVERITY_HIGH => log::Level::Error,
+        gl::DEBUG_SEVERITY_MEDIUM => log::Level::Warn,
+        gl::DEBUG_SEVERITY_LOW => log::Level::Info,
+        _ => log::Level::Debug,
     };
+    log::log!(
+        target: logging::GL,
+        level,
+        "GL {} {} ({}): {}",
+        debug_source(source),
+        debug_type(message_type),
+        id,
+        message
+    );
+}
+
+/// Names a GL object in debug output and GL debuggers. `label` must be
+/// NUL-terminated.
+pub(crate) fn label_object(
+    gl: &gl::Gl,
+    identifier: GLenum,
+    name: GLuint,
+    label: &'static [u8],
+) {
+    if gl.ObjectLabel.is_loaded() {
+        unsafe { gl.ObjectLabel(identifier, name, -1, label.as_ptr() as *const GLchar) };
+    }
+}
+
+/// One of the strings `glGetString` describes the context with, or `None`
+/// if there is no such string or no context is current.
+fn get_string(gl: &gl::Gl, name: GLenum) -> Option<String> {
+    let string = unsafe { gl.GetString(name) };
+    if string.is_null() {
+        return None;
+    }
+    let bytes = unsafe { CStr::from_ptr(string as *const GLchar) }.to_bytes();
+    Some(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Loads the GL functions of the current context and sets up what lepton
+/// draws with. With `debug` set, the driver's messages about it are passed
+/// on to the log as they happen, if it supports `KHR_debug`.
+pub fn load<F>(get_proc_address: F, debug: bool) -> Result<Gl, String>
+where
+    F: FnMut(&'static str) -> *const c_void,
+{
+    let gl = gl::Gl::load_with(get_proc_address);
+
+    let version = get_string(&gl, gl::VERSION)
+        .ok_or_else(|| "Failed to get the OpenGL version; is a context current?".to_owned())?;
 
-    println!("OpenGL version {}", version);
+    log::info!(target: logging::GL, "OpenGL version {}", version);
 
-    let mut fb = 0;
+    if debug {
+        if gl.DebugMessageCallback.is_loaded() {
+            unsafe {
+                gl.Enable(gl::DEBUG_OUTPUT);
+                // Report messages from within the call that caused them, so
+                // they line up with the rest of the log.
+                gl.Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+                gl.DebugMessageCallback(Some(debug_message_callback), std::ptr::null());
+            }
+        } else {
+            log::warn!(
+                target: logging::GL,
+                "GL debug output requested, but KHR_debug is not supported"
+            );
+        }
+    }
+
+    let shading_language_version = get_string(&gl, gl::SHADING_LANGUAGE_VERSION).unwrap_or_default();
+    log::info!(target: logging::GL, "GLSL version {}", shading_language_version);
+    let quad = QuadRenderer::new(&gl, version.starts_with("OpenGL ES"), &shading_language_version)?;
+
+    let mut overlay_texture = 0;
     unsafe {
-        gl.GenFramebuffers(1, &mut fb);
+        gl.GenTextures(1, &mut overlay_texture);
+        // Names only become objects, which can be labelled, once bound.
+        gl.BindTexture(gl::TEXTURE_2D, overlay_texture);
+        gl.BindTexture(gl::TEXTURE_2D, 0);
     }
+    label_object(&gl, gl::TEXTURE, overlay_texture, b"lepton overlay\0");
 
-    Gl { gl, fb }
+    Ok(Gl {
+        gl,
+        quad,
+        overlay_texture,
+    })
 }
 
 impl Gl {
-    #[track_caller]
-    pub fn assert_no_error(&self) {
+    /// Reports the first error raised since the last check, blaming `call`.
+    pub fn check_error(&self, call: &'static str) -> Result<(), GlError> {
+        let code = unsafe { self.gl.GetError() };
+        if code == gl::NO_ERROR {
+            Ok(())
+        } else {
+            Err(GlError { call, code })
+        }
+    }
+
+    pub fn clear(&self, [red, green, blue, alpha]: [f32; 4]) {
         unsafe {
-            assert_eq!(self.gl.GetError(), gl::NO_ERROR);
+            self.gl.ClearColor(red, green, blue, alpha);
+            self.gl.Clear(gl::COLOR_BUFFER_BIT);
         }
     }
 
+    pub fn flush(&self) {
+        unsafe { self.gl.Flush() };
+    }
+
+    /// Reads back the current framebuffer, of `size`, as RGBA pixels stored
+    /// top row first.
+    pub fn read_pixels(&self, size: Size2D<i32>) -> Result<Vec<u8>, GlError> {
+        let stride = size.width as usize * 4;
+        let mut pixels = vec![0; stride * size.height as usize];
+        unsafe {
+            self.gl.ReadPixels(
+                0,
+                0,
+                size.width,
+                size.height,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut c_void,
+            );
+        }
+        self.check_error("glReadPixels")?;
+        // GL reads the bottom row first.
+        Ok(pixels.chunks_exact(stride).rev().flatten().copied().collect())
+    }
+
+    /// Draws one of Servo's surfaces, of `size`, into the current
+    /// framebuffer.
     pub fn draw_texture(
         &self,
         device: &surfman::Device,
         texture: u32,
         size: Size2D<i32>,
-        dest: Size2D<i32>,
-    ) {
+        quad: &Quad,
+        framebuffer_size: Size2D<i32>,
+    ) -> Result<(), GlError> {
+        let target = device.surface_gl_texture_target();
+        self.quad.draw(&self.gl, texture, target, size, framebuffer_size, quad);
+        self.check_error("drawing a surface")?;
+        unsafe { self.gl.Flush() };
+        Ok(())
+    }
+
+    /// Draws RGBA `pixels`, stored top row first, into the current
+    /// framebuffer with their top left corner at `origin`.
+    pub fn draw_pixels(
+        &self,
+        pixels: &[u8],
+        size: Size2D<i32>,
+        origin: Point2D<i32>,
+        framebuffer_size: Size2D<i32>,
+    ) -> Result<(), GlError> {
         unsafe {
-            self.gl.BindFramebuffer(gl::READ_FRAMEBUFFER, self.fb);
-            self.assert_no_error();
-            self.gl.FramebufferTexture2D(
-                gl::READ_FRAMEBUFFER,
-                gl::COLOR_ATTACHMENT0,
-                device.surface_gl_texture_target(),
-                //gl::TEXTURE_2D,
-                texture,
+            self.gl.BindTexture(gl::TEXTURE_2D, self.overlay_texture);
+            self.gl.TexImage2D(
+                gl::TEXTURE_2D,
                 0,
+                gl::RGBA as i32,
+                size.width,
+                size.height,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_ptr() as *const c_void,
             );
-            self.assert_no_error();
-            self.gl.BlitFramebuffer(
-                0, 0, size.width, size.height,
-                0, 0, dest.width, dest.height,
-                gl::COLOR_BUFFER_BIT,
-                gl::LINEAR,
-            );
-            self.assert_no_error();
-            self.gl.BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
-            self.assert_no_error();
-            self.gl.Flush();
+            self.gl.BindTexture(gl::TEXTURE_2D, 0);
         }
+        self.check_error("glTexImage2D")?;
+        let quad = Quad {
+            dest: Box2D::from_origin_and_size(origin.to_f32(), size.to_f32()),
+            opacity: 1.0,
+            flip_y: true,
+        };
+        self.quad.draw(&self.gl, self.overlay_texture, gl::TEXTURE_2D, size, framebuffer_size, &quad);
+        self.check_error("drawing an overlay")?;
+        unsafe { self.gl.Flush() };
+        Ok(())
     }
 }
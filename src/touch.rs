@@ -0,0 +1,189 @@
+//! Bookkeeping for the fingers currently on a touchscreen.
+
+use servo::webrender_api::units::DevicePoint;
+
+/// A finger on the screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Touch {
+    /// What the toolkit calls it, which may be a pointer value.
+    id: u64,
+    /// What the page calls it: the smallest number no other finger down has.
+    index: i32,
+    position: DevicePoint,
+}
+
+#[derive(Default)]
+pub struct TouchTracker {
+    /// Active touches, in the order they started.
+    touches: Vec<Touch>,
+    /// The distance between the two fingers when the pinch was last updated.
+    pinch_distance: Option<f32>,
+}
+
+impl TouchTracker {
+    /// Whether two fingers are down and being treated as a pinch, in which
+    /// case none of the touches are delivered to the page.
+    pub fn is_pinching(&self) -> bool {
+        self.pinch_distance.is_some()
+    }
+
+    /// The first finger down, which is the one that emulates the mouse.
+    pub fn is_primary(&self, id: u64) -> bool {
+        self.touches.first().map(|touch| touch.id) == Some(id)
+    }
+
+    /// The number the page knows the touch `id` by, if it is down. Toolkit
+    /// IDs don't fit in a `TouchId`, so each finger gets a small index
+    /// instead, which is free again once it is lifted.
+    pub fn index(&self, id: u64) -> Option<i32> {
+        self.touches
+            .iter()
+            .find(|touch| touch.id == id)
+            .map(|touch| touch.index)
+    }
+
+    fn distance(&self) -> Option<f32> {
+        match self.touches[..] {
+            [a, b] => Some((a.position - b.position).length()),
+            _ => None,
+        }
+    }
+
+    /// Records a new touch and returns its index. If it turns the gesture
+    /// into a pinch, also returns the touches the page has already seen, by
+    /// index, so they can be cancelled.
+    pub fn touch_down(&mut self, id: u64, position: DevicePoint) -> (i32, Vec<(i32, DevicePoint)>) {
+        if let Some(index) = self.index(id) {
+            // A touch starting twice is the toolkit's mistake; keep the
+            // first one.
+            return (index, vec![]);
+        }
+        let seen: Vec<_> = self
+            .touches
+            .iter()
+            .map(|touch| (touch.index, touch.position))
+            .collect();
+        let index = (0..)
+            .find(|index| !seen.iter().any(|&(used, _)| used == *index))
+            .unwrap_or_default();
+        self.touches.push(Touch {
+            id,
+            index,
+            position,
+        });
+        if self.pinch_distance.is_none() {
+            self.pinch_distance = self.distance();
+            if self.pinch_distance.is_some() {
+                return (index, seen);
+            }
+        }
+        (index, vec![])
+    }
+
+    /// Records a touch moving. While pinching, returns how much the
+    /// distance between the fingers changed since the last update.
+    pub fn touch_move(&mut self, id: u64, position: DevicePoint) -> Option<f32> {
+        if let Some(touch) = self.touches.iter_mut().find(|touch| touch.id == id) {
+            touch.position = position;
+        }
+        let old_distance = self.pinch_distance?;
+        let new_distance = self.distance()?;
+        self.pinch_distance = Some(new_distance);
+        (old_distance > 0.0).then(|| new_distance / old_distance)
+    }
+
+    /// Records a touch ending or being cancelled, which frees its index. A
+    /// pinch lasts until every finger is lifted.
+    pub fn touch_up(&mut self, id: u64) {
+        self.touches.retain(|touch| touch.id != id);
+        if self.touches.is_empty() {
+            self.pinch_distance = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A GTK event sequence, which is a pointer.
+    const BIG_ID: u64 = 0x7f12_3456_7890;
+
+    fn point(x: f32, y: f32) -> DevicePoint {
+        DevicePoint::new(x, y)
+    }
+
+    #[test]
+    fn fingers_get_the_smallest_free_index() {
+        let mut touches = TouchTracker::default();
+        assert_eq!(touches.touch_down(BIG_ID, point(0.0, 0.0)).0, 0);
+        assert_eq!(touches.touch_down(BIG_ID + 8, point(10.0, 0.0)).0, 1);
+        assert_eq!(touches.touch_down(BIG_ID + 16, point(20.0, 0.0)).0, 2);
+        touches.touch_up(BIG_ID + 8);
+        assert_eq!(touches.index(BIG_ID + 8), None);
+        assert_eq!(touches.touch_down(BIG_ID + 24, point(30.0, 0.0)).0, 1);
+        assert_eq!(touches.index(BIG_ID), Some(0));
+        assert_eq!(touches.index(BIG_ID + 16), Some(2));
+    }
+
+    #[test]
+    fn a_cancelled_touch_frees_its_index() {
+        let mut touches = TouchTracker::default();
+        touches.touch_down(BIG_ID, point(0.0, 0.0));
+        touches.touch_up(BIG_ID);
+        assert_eq!(touches.index(BIG_ID), None);
+        assert_eq!(touches.touch_down(BIG_ID + 8, point(0.0, 0.0)).0, 0);
+        assert!(touches.is_primary(BIG_ID + 8));
+    }
+
+    #[test]
+    fn one_finger_is_not_a_pinch() {
+        let mut touches = TouchTracker::default();
+        assert_eq!(touches.touch_down(1, point(0.0, 0.0)), (0, vec![]));
+        assert!(!touches.is_pinching());
+        assert!(touches.is_primary(1));
+        assert_eq!(touches.touch_move(1, point(5.0, 5.0)), None);
+    }
+
+    #[test]
+    fn a_second_finger_cancels_the_first() {
+        let mut touches = TouchTracker::default();
+        touches.touch_down(1, point(0.0, 0.0));
+        touches.touch_move(1, point(10.0, 0.0));
+        let (index, cancelled) = touches.touch_down(2, point(20.0, 0.0));
+        assert_eq!(index, 1);
+        assert_eq!(cancelled, vec![(0, point(10.0, 0.0))]);
+        assert!(touches.is_pinching());
+    }
+
+    #[test]
+    fn pinching_reports_the_change_in_distance() {
+        let mut touches = TouchTracker::default();
+        touches.touch_down(1, point(0.0, 0.0));
+        touches.touch_down(2, point(10.0, 0.0));
+        assert_eq!(touches.touch_move(2, point(20.0, 0.0)), Some(2.0));
+        assert_eq!(touches.touch_move(1, point(10.0, 0.0)), Some(0.5));
+    }
+
+    #[test]
+    fn a_pinch_lasts_until_every_finger_is_lifted() {
+        let mut touches = TouchTracker::default();
+        touches.touch_down(1, point(0.0, 0.0));
+        touches.touch_down(2, point(10.0, 0.0));
+        touches.touch_up(1);
+        assert!(touches.is_pinching());
+        // With one finger left there is no distance to compare.
+        assert_eq!(touches.touch_move(2, point(30.0, 0.0)), None);
+        touches.touch_up(2);
+        assert!(!touches.is_pinching());
+    }
+
+    #[test]
+    fn a_third_finger_does_not_restart_the_pinch() {
+        let mut touches = TouchTracker::default();
+        touches.touch_down(1, point(0.0, 0.0));
+        touches.touch_down(2, point(10.0, 0.0));
+        assert_eq!(touches.touch_down(3, point(20.0, 0.0)), (2, vec![]));
+        assert!(touches.is_pinching());
+    }
+}
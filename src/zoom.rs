@@ -0,0 +1,128 @@
+//! Page zoom levels, remembered per origin the way browsers do.
+
+use servo::servo_url::ImmutableOrigin;
+use std::collections::HashMap;
+
+/// The bounds Servo's compositor clamps page zoom to.
+pub const MIN_ZOOM: f32 = 0.1;
+pub const MAX_ZOOM: f32 = 8.0;
+
+/// How much one zoom shortcut or one wheel notch changes the zoom level.
+pub const ZOOM_STEP: f32 = 1.1;
+
+#[derive(Default)]
+pub struct ZoomLevels {
+    levels: HashMap<ImmutableOrigin, f32>,
+    origin: Option<ImmutableOrigin>,
+}
+
+impl ZoomLevels {
+    /// The zoom level of the page currently shown.
+    pub fn current(&self) -> f32 {
+        self.origin
+            .as_ref()
+            .and_then(|origin| self.levels.get(origin))
+            .copied()
+            .unwrap_or(1.0)
+    }
+
+    /// The zoom level `steps` zoom steps away from the current one; negative
+    /// steps zoom out. It isn't clamped until it is `set`.
+    pub fn stepped(&self, steps: f32) -> f32 {
+        self.current() * ZOOM_STEP.powf(steps)
+    }
+
+    /// Sets the zoom level for the current origin and returns it, clamped to
+    /// what Servo supports.
+    pub fn set(&mut self, level: f32) -> f32 {
+        let level = level.clamp(MIN_ZOOM, MAX_ZOOM);
+        if let Some(origin) = self.origin.clone() {
+            if level == 1.0 {
+                self.levels.remove(&origin);
+            } else {
+                self.levels.insert(origin, level);
+            }
+        }
+        level
+    }
+
+    /// Records a navigation to `origin`, returning the zoom level to apply if
+    /// it differs from the one the previous page was shown at.
+    pub fn navigated_to(&mut self, origin: ImmutableOrigin) -> Option<f32> {
+        let old_level = self.current();
+        self.origin = Some(origin);
+        let new_level = self.current();
+        (new_level != old_level).then_some(new_level)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use servo::servo_url::ServoUrl;
+
+    fn origin(url: &str) -> ImmutableOrigin {
+        ServoUrl::parse(url).unwrap().origin()
+    }
+
+    fn approx_eq(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    #[test]
+    fn set_clamps_to_what_servo_supports() {
+        let mut levels = ZoomLevels::default();
+        levels.navigated_to(origin("https://example.com/"));
+        assert_eq!(levels.set(100.0), MAX_ZOOM);
+        assert_eq!(levels.current(), MAX_ZOOM);
+        assert_eq!(levels.set(0.0), MIN_ZOOM);
+        assert_eq!(levels.current(), MIN_ZOOM);
+        assert_eq!(levels.set(2.0), 2.0);
+    }
+
+    #[test]
+    fn steps_are_relative_to_the_current_level() {
+        let mut levels = ZoomLevels::default();
+        levels.navigated_to(origin("https://example.com/"));
+        assert!(approx_eq(levels.stepped(1.0), ZOOM_STEP));
+        assert!(approx_eq(levels.stepped(-1.0), 1.0 / ZOOM_STEP));
+        levels.set(2.0);
+        assert!(approx_eq(levels.stepped(2.0), 2.0 * ZOOM_STEP * ZOOM_STEP));
+        assert!(approx_eq(levels.set(levels.stepped(100.0)), MAX_ZOOM));
+    }
+
+    #[test]
+    fn navigating_restores_each_origins_level() {
+        let mut levels = ZoomLevels::default();
+        assert_eq!(levels.navigated_to(origin("https://a.example/")), None);
+        levels.set(2.0);
+        assert_eq!(levels.navigated_to(origin("https://b.example/")), Some(1.0));
+        assert_eq!(levels.current(), 1.0);
+        assert_eq!(
+            levels.navigated_to(origin("https://a.example/other")),
+            Some(2.0)
+        );
+    }
+
+    #[test]
+    fn navigating_to_the_same_level_changes_nothing() {
+        let mut levels = ZoomLevels::default();
+        levels.navigated_to(origin("https://a.example/"));
+        levels.set(2.0);
+        assert_eq!(levels.navigated_to(origin("https://a.example/next")), None);
+        levels.navigated_to(origin("https://b.example/"));
+        levels.set(2.0);
+        // Another origin at the same level needs no new zoom either.
+        assert_eq!(levels.navigated_to(origin("https://a.example/")), None);
+        assert_eq!(levels.current(), 2.0);
+    }
+
+    #[test]
+    fn the_default_level_is_forgotten() {
+        let mut levels = ZoomLevels::default();
+        levels.navigated_to(origin("https://a.example/"));
+        levels.set(2.0);
+        levels.set(1.0);
+        assert!(levels.levels.is_empty());
+    }
+}
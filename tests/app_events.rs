@@ -0,0 +1,82 @@
+//! Drives an `App` with a scripted backend, to check how window events get
+//! to Servo.
+//!
+//! There can only be one Servo in a process, so everything is checked from
+//! the one script, which ends by closing the window.
+
+mod support;
+
+use lepton::app::App;
+use lepton::backend::{BackendEvent, LoopControl, WindowBackend};
+use lepton::config::Config;
+use servo::webrender_api::units::DeviceIntSize;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use support::{Call, MockBackend};
+
+/// What the app looked like after handling an event.
+#[derive(Debug)]
+struct Step {
+    event: String,
+    control: LoopControl,
+    page_size: DeviceIntSize,
+    /// How many batches of events had been handed to Servo.
+    batches: u64,
+}
+
+#[test]
+fn window_events_reach_servo_at_the_end_of_each_turn() {
+    let initial_size = DeviceIntSize::new(800, 600);
+    let new_size = DeviceIntSize::new(1024, 768);
+    let script = vec![BackendEvent::Resized(new_size), BackendEvent::CloseRequested];
+    let backend = MockBackend::new(initial_size, script);
+    let calls = backend.calls();
+    let url = "data:text/html,<p>Hello from lepton</p>".to_owned();
+    let config = Config::from_args(std::iter::once(url)).unwrap();
+    let mut app = App::new(&backend, config);
+    assert_eq!(app.page_size(), initial_size);
+
+    let steps = Rc::new(RefCell::new(vec![]));
+    let recorded_steps = steps.clone();
+    backend.run(move |backend: &dyn WindowBackend, event| {
+        let name = format!("{:?}", event);
+        let control = app.handle_event(backend, event);
+        recorded_steps.borrow_mut().push(Step {
+            event: name,
+            control,
+            page_size: app.page_size(),
+            batches: app.input_stats().batches,
+        });
+        control
+    });
+    let steps = steps.borrow();
+
+    // A resize takes effect at once, but only reaches Servo with the rest
+    // of the turn's events.
+    let resized = &steps[0];
+    assert!(resized.event.starts_with("Resized"), "{:?}", steps);
+    assert_eq!(resized.page_size, new_size);
+    assert_eq!(resized.batches, 0);
+    let cleared = &steps[1];
+    assert_eq!(cleared.event, "EventsCleared");
+    assert_eq!(cleared.batches, 1);
+    // The window has to be redrawn at its new size.
+    assert!(calls.borrow().contains(&Call::RequestRedraw));
+
+    // Closing the window asks Servo to quit, and the loop exits once it has
+    // shut down, which takes handing it the events queued until then.
+    let closed = steps
+        .iter()
+        .position(|step| step.event == "CloseRequested")
+        .expect("The window wasn't closed");
+    assert_ne!(steps[closed].control, LoopControl::Exit);
+    assert_eq!(steps[closed + 1].event, "EventsCleared");
+    assert!(steps[closed + 1].batches > steps[closed].batches);
+    let last = steps.last().unwrap();
+    assert_eq!(last.event, "EventsCleared");
+    assert_eq!(last.control, LoopControl::Exit);
+    assert!(steps[..steps.len() - 1]
+        .iter()
+        .all(|step| step.control != LoopControl::Exit));
+}
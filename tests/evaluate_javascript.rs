@@ -0,0 +1,131 @@
+//! Runs scripts with `App::evaluate_javascript` in a local page, in headless
+//! mode, and checks the values they return, the exceptions they throw, and
+//! what becomes of values JSON can't represent.
+//!
+//! There can only be one Servo in a process, so every script runs in the one
+//! test, one after the other, and the results are checked once all are in.
+
+use lepton::app::App;
+use lepton::backend::headless::HeadlessBackend;
+use lepton::backend::LoopControl;
+use lepton::config::Config;
+use lepton::script::{JavaScriptError, JavaScriptResult};
+use serde_json::{json, Value};
+use servo::webrender_api::units::DeviceIntSize;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+const PAGE: &str = concat!("file://", env!("CARGO_MANIFEST_DIR"), "/tests/evaluate_javascript.html");
+
+/// How long the page gets to load before the test gives up.
+const LOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Scripts to run once the page has loaded, in order.
+const SCRIPTS: &[&str] = &[
+    "1 + 2",
+    "add(0.5, 0.25)",
+    "document.getElementById('greeting').textContent",
+    "({ title: document.title, items: window.items })",
+    "var answer = 42; answer",
+    "answer + 1",
+    "throw new Error('boom')",
+    "missingFunction()",
+    "throw 'plain string'",
+    "undefined",
+    "(function () {})",
+    "Symbol('lepton')",
+    "NaN",
+    "({ kept: 1, dropped: undefined, method() {} })",
+    "const cyclic = {}; cyclic.self = cyclic; cyclic",
+    "10n",
+];
+
+#[derive(Default)]
+struct Results {
+    answers: Vec<(&'static str, JavaScriptResult)>,
+    done: bool,
+}
+
+/// Asks the page whether it has loaded until it has, then runs the scripts.
+fn wait_for_load(app: &mut App, deadline: Instant, results: Rc<RefCell<Results>>) {
+    let webview = app.webview_id();
+    app.evaluate_javascript(webview, "document.readyState", move |app, result| {
+        if result == Ok(json!("complete")) {
+            run_script(app, 0, results);
+        } else {
+            assert!(Instant::now() < deadline, "The page never loaded: {:?}", result);
+            wait_for_load(app, deadline, results);
+        }
+    });
+}
+
+fn run_script(app: &mut App, index: usize, results: Rc<RefCell<Results>>) {
+    let Some(&source) = SCRIPTS.get(index) else {
+        results.borrow_mut().done = true;
+        return;
+    };
+    let webview = app.webview_id();
+    app.evaluate_javascript(webview, source, move |app, result| {
+        results.borrow_mut().answers.push((source, result));
+        run_script(app, index + 1, results);
+    });
+}
+
+fn is_type_error(result: &JavaScriptResult) -> bool {
+    matches!(result, Err(JavaScriptError::Exception(message)) if message.starts_with("TypeError"))
+}
+
+#[test]
+fn scripts_return_json_values_and_exceptions() {
+    let config = Config::from_args(std::iter::once(PAGE.to_owned())).unwrap();
+    let backend = HeadlessBackend::new(DeviceIntSize::new(800, 600));
+    let mut app = App::new(&backend, config);
+    let results = Rc::new(RefCell::new(Results::default()));
+    wait_for_load(&mut app, Instant::now() + LOAD_TIMEOUT, results.clone());
+
+    let finished = results.clone();
+    backend.run(move |backend, event| {
+        let control = app.handle_event(backend, event);
+        if finished.borrow().done {
+            return LoopControl::Exit;
+        }
+        control
+    });
+
+    let results = results.borrow();
+    assert!(results.done, "Servo shut down before every script had run");
+    let answers: Vec<_> = results.answers.iter().map(|(_, result)| result).collect();
+    assert_eq!(answers.len(), SCRIPTS.len());
+
+    // Values come back as JSON.
+    assert_eq!(answers[0], &Ok(json!(3)));
+    assert_eq!(answers[1], &Ok(json!(0.75)));
+    assert_eq!(answers[2], &Ok(json!("Hello from lepton")));
+    assert_eq!(
+        answers[3],
+        &Ok(json!({ "title": "evaluate_javascript", "items": [1, "two", null, true] }))
+    );
+    // Scripts run in the global scope, so declarations carry over.
+    assert_eq!(answers[4], &Ok(json!(42)));
+    assert_eq!(answers[5], &Ok(json!(43)));
+
+    // Exceptions are passed on as their string conversion.
+    assert_eq!(answers[6], &Err(JavaScriptError::Exception("Error: boom".to_owned())));
+    assert_eq!(
+        answers[7],
+        &Err(JavaScriptError::Exception("ReferenceError: missingFunction is not defined".to_owned()))
+    );
+    assert_eq!(answers[8], &Err(JavaScriptError::Exception("plain string".to_owned())));
+
+    // What JSON has no room for becomes null, or is left out of objects.
+    assert_eq!(answers[9], &Ok(Value::Null));
+    assert_eq!(answers[10], &Ok(Value::Null));
+    assert_eq!(answers[11], &Ok(Value::Null));
+    assert_eq!(answers[12], &Ok(Value::Null));
+    assert_eq!(answers[13], &Ok(json!({ "kept": 1 })));
+
+    // What JSON can't serialize at all fails the way `JSON.stringify` does.
+    assert!(is_type_error(answers[14]), "{:?}", answers[14]);
+    assert!(is_type_error(answers[15]), "{:?}", answers[15]);
+}
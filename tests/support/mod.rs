@@ -0,0 +1,207 @@
+//! A backend for tests, which plays a script of events to the embedder and
+//! records what the embedder asks of it.
+
+use lepton::backend::{BackendEvent, LoopControl, ScreenGeometry, WindowBackend};
+use lepton::clipboard::{Clipboard, MemoryClipboard};
+use lepton::menu::MenuItem;
+use servo::webrender_api::units::{DeviceIntPoint, DeviceIntRect, DeviceIntSize};
+use servo::{Cursor, EventLoopWaker, InputMethodType};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+/// How long the embedder gets to exit once the script has been played.
+const EXIT_TIMEOUT: Duration = Duration::from_secs(60);
+
+struct Waker(Sender<()>);
+impl EventLoopWaker for Waker {
+    fn clone_box(&self) -> Box<dyn EventLoopWaker> {
+        Box::new(Waker(self.0.clone()))
+    }
+    fn wake(&self) {
+        let _ = self.0.send(());
+    }
+}
+
+/// Something the embedder asked the backend to do.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Call {
+    MakeCurrent,
+    Present,
+    SetCursor(Cursor),
+    SetTitle(String),
+    ShowIme(InputMethodType),
+    HideIme,
+    ShowMenu(Vec<String>),
+    PickFiles { extensions: Vec<String>, multiple: bool },
+    RequestRedraw,
+}
+
+/// A window that isn't there. Each scripted event is delivered in a turn of
+/// the event loop of its own, followed by `BackendEvent::EventsCleared`;
+/// once they have all been, the loop carries on with Servo's wake-ups until
+/// the handler exits.
+pub struct MockBackend {
+    size: Cell<DeviceIntSize>,
+    script: RefCell<VecDeque<BackendEvent>>,
+    calls: Rc<RefCell<Vec<Call>>>,
+    redraw_requested: Cell<bool>,
+    /// When the embedder will have had long enough to exit, once the
+    /// script has been played.
+    exit_deadline: Cell<Option<Instant>>,
+    sender: Sender<()>,
+    receiver: Receiver<()>,
+    clipboard: MemoryClipboard,
+}
+
+impl MockBackend {
+    pub fn new(size: DeviceIntSize, script: Vec<BackendEvent>) -> MockBackend {
+        let (sender, receiver) = mpsc::channel();
+        MockBackend {
+            size: Cell::new(size),
+            script: RefCell::new(script.into()),
+            calls: Rc::new(RefCell::new(vec![])),
+            redraw_requested: Cell::new(false),
+            exit_deadline: Cell::new(None),
+            sender,
+            receiver,
+            clipboard: MemoryClipboard::default(),
+        }
+    }
+
+    /// What the embedder has asked for so far, which stays readable after
+    /// `run` has consumed the backend.
+    pub fn calls(&self) -> Rc<RefCell<Vec<Call>>> {
+        self.calls.clone()
+    }
+
+    fn record(&self, call: Call) {
+        self.calls.borrow_mut().push(call);
+    }
+
+    fn next_event(&self, control: LoopControl) -> BackendEvent {
+        if self.redraw_requested.take() {
+            return BackendEvent::RedrawRequested;
+        }
+        if let Some(event) = self.script.borrow_mut().pop_front() {
+            // The window takes its new size before telling anyone.
+            if let BackendEvent::Resized(size) = event {
+                self.size.set(size);
+            }
+            return event;
+        }
+        let exit_deadline = self
+            .exit_deadline
+            .get()
+            .unwrap_or_else(|| Instant::now() + EXIT_TIMEOUT);
+        self.exit_deadline.set(Some(exit_deadline));
+        let deadline = match control {
+            LoopControl::WaitUntil(due) if due < exit_deadline => due,
+            _ => exit_deadline,
+        };
+        match self.receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+            Ok(()) => BackendEvent::Wake,
+            Err(RecvTimeoutError::Timeout) if deadline < exit_deadline => BackendEvent::Timeout,
+            Err(_) => panic!("The embedder didn't exit once the script was played"),
+        }
+    }
+}
+
+impl WindowBackend for MockBackend {
+    fn native_context(&self) -> Option<surfman::NativeContext> {
+        None
+    }
+
+    fn connection(&self) -> surfman::Connection {
+        surfman::Connection::new().expect("Failed to create connection")
+    }
+
+    fn inner_size(&self) -> DeviceIntSize {
+        self.size.get()
+    }
+
+    fn screen_geometry(&self) -> ScreenGeometry {
+        let size = self.size.get();
+        ScreenGeometry {
+            screen_size: size,
+            available_screen_size: size,
+            window_rect: DeviceIntRect::from_origin_and_size(DeviceIntPoint::zero(), size),
+            refresh_rate: None,
+        }
+    }
+
+    fn scale_factor(&self) -> f32 {
+        1.0
+    }
+
+    fn make_current(&self) {
+        self.record(Call::MakeCurrent);
+    }
+
+    fn present(&self) {
+        self.record(Call::Present);
+    }
+
+    fn set_cursor(&self, cursor: Cursor) {
+        self.record(Call::SetCursor(cursor));
+    }
+
+    fn set_title(&self, title: &str) {
+        self.record(Call::SetTitle(title.to_owned()));
+    }
+
+    fn clipboard(&self) -> &dyn Clipboard {
+        &self.clipboard
+    }
+
+    fn show_ime(&self, input_type: InputMethodType, _area: DeviceIntRect) {
+        self.record(Call::ShowIme(input_type));
+    }
+
+    fn hide_ime(&self) {
+        self.record(Call::HideIme);
+    }
+
+    fn show_menu(&self, _anchor: DeviceIntRect, items: &[MenuItem]) -> bool {
+        let labels = items.iter().map(|item| item.label.clone()).collect();
+        self.record(Call::ShowMenu(labels));
+        false
+    }
+
+    fn pick_files(&self, extensions: &[String], multiple: bool) -> bool {
+        self.record(Call::PickFiles {
+            extensions: extensions.to_vec(),
+            multiple,
+        });
+        false
+    }
+
+    fn request_redraw(&self) {
+        self.record(Call::RequestRedraw);
+        self.redraw_requested.set(true);
+    }
+
+    fn create_event_loop_waker(&self) -> Box<dyn EventLoopWaker> {
+        Box::new(Waker(self.sender.clone()))
+    }
+
+    fn run<H>(self, mut handler: H)
+    where
+        H: FnMut(&dyn WindowBackend, BackendEvent) -> LoopControl + 'static,
+    {
+        let mut control = LoopControl::Wait;
+        loop {
+            let event = self.next_event(control);
+            control = handler(&self, event);
+            if control == LoopControl::Exit {
+                break;
+            }
+            control = handler(&self, BackendEvent::EventsCleared);
+            if control == LoopControl::Exit {
+                break;
+            }
+        }
+    }
+}